@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write, BufWriter};
 use std::path::{Path, PathBuf};
@@ -8,19 +9,35 @@ use serde::Deserialize;
 use serde_json::{json, Map, Value};
 
 use crate::splunk_utils::{
-    splunk_helper::SplunkHelper,
-    http_event_collector::HttpEventCollector
+    splunk_helper::{SplunkHelper, HEC_INPUT_NAME},
+    hec_endpoints::{Balance, EndpointPool, HecEndpoint},
+    retry::{RetryPolicy, describe_error},
+    rate_limit::RateLimiter,
+    http_event_collector::{EventOrigin, HttpEventCollector},
+    dead_letter::DeadLetterQueue,
+    tls::TlsSettings,
+    index_settings::IndexSettings,
+    reconcile::{FILES_PER_SEARCH, FileStatus, count_query, parse_counts, reconcile},
 };
 
 use crate::utils::{
+    checkpoint::{CheckpointJournal, FileProgress},
+    archive::{ArchiveKind, ChannelReader, send_member, stream_members},
+    compression::{decompress, inner_extension, open_decompressed},
+    decoding::decoded_input,
+    evtx_reader::open_evtx,
     file_matcher::FileTuple,
-    utils::{LossyUtf8Reader, extract_host_from_record, extract_timestamp_from_record, normalize_host, hash_path, is_valid_hec_time},
-    vrl::{VrlChain, compile_vrl_chain, apply_vrl_chain_to_record}
+    secrets::{Secret, interpolate_env, prompt_secret, read_secret_file},
+    raw_parser::{merge_lines, raw_record},
+    utils::{extract_host_from_record, extract_timestamp_from_record, normalize_host, hash_path, is_valid_hec_time},
+    vrl::{VrlChain, SplunkOverrides, compile_vrl_chain, apply_vrl_chain_to_record, event_metadata}
 };
 
-use std::sync::Arc;
-use std::thread;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::{Arc, Mutex};
 use crossbeam_channel as chan;
+use rayon::prelude::*;
 use reqwest::blocking::Client; 
 use std::io::Read;
 
@@ -28,13 +45,87 @@ use std::io::Read;
 // Structure of the Splunk configuration YAML
 #[derive(Debug, Deserialize)]
 struct SplunkSection {
+    /// Management API settings (not needed in direct HEC mode)
+    #[serde(default)]
     host: String,
+    #[serde(default)]
     user: String,
-    password: String,
+    #[serde(default)]
+    password: Secret,
+    /// File holding the password (takes precedence over `password`)
+    password_file: Option<PathBuf>,
+    #[serde(default = "default_mport")]
     mport: u16,
+    /// https (true) or plain http (false) for the management API, and for HEC unless `hec_ssl` is set
+    #[serde(default)]
     ssl: bool,
+    /// https (true) or plain http (false) for HEC endpoints without scheme. Defaults to `ssl`
+    hec_ssl: Option<bool>,
+    #[serde(flatten)]
+    tls: TlsSettings,
+    /// HEC endpoint(s) ("https://hec.example.com:8088" or a host name), one or a list.
+    /// Defaults to `host`.
+    hec_url: Option<HecUrls>,
+    /// How batches are spread over several HEC endpoints: round_robin (default) or least_loaded
+    hec_balance: Option<String>,
+    /// Direct HEC mode: existing HEC token. When set, the management API is not used.
+    hec_token: Option<Secret>,
+    /// File holding the HEC token (takes precedence over `hec_token`, enables direct HEC mode)
+    token_file: Option<PathBuf>,
+    /// HEC port when `hec_url` has none
+    hec_port: Option<u16>,
+    /// Enable HEC indexer acknowledgement (useACK) on the json2splunk-rs input
+    #[serde(default)]
+    use_ack: bool,
+    /// Seconds to wait for an ack before re-sending a batch
+    #[serde(default = "default_ack_timeout")]
+    ack_timeout: u64,
+    /// Retries of network errors, 429 and 5xx
+    #[serde(default)]
+    retry: RetryPolicy,
+    /// Compress the HEC request bodies with gzip
+    #[serde(default)]
+    gzip: bool,
+    /// Gzip level, 1 (fast) to 9 (small)
+    #[serde(default = "default_gzip_level")]
+    gzip_level: u32,
+    /// Retention, size, paths, datatype and app of the indexes created by json2splunk-rs
+    #[serde(default)]
+    index_settings: IndexSettings,
 }
 
+/// `hec_url: host` or `hec_url: [host1, host2]`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum HecUrls {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HecUrls {
+    fn list(&self) -> Vec<&str> {
+        match self {
+            HecUrls::One(url) => vec![url.as_str()],
+            HecUrls::Many(urls) => urls.iter().map(|u| u.as_str()).collect(),
+        }
+    }
+}
+
+fn default_ack_timeout() -> u64 {
+    300
+}
+
+fn default_gzip_level() -> u32 {
+    6
+}
+
+fn default_mport() -> u16 {
+    8089
+}
+
+/// Default port of the HTTP Event Collector
+const DEFAULT_HEC_PORT: u16 = 8088;
+
 #[derive(Debug, Deserialize)]
 struct SplunkConfig {
     splunk: SplunkSection,
@@ -51,6 +142,8 @@ struct EventContext {
     timestamp_format: String,
     artifact: String,
     sourcefile: String,
+    /// Per-pattern index (None = index of the HEC input URL, i.e. --index)
+    index: Option<String>,
 }
 
 /// Main struct
@@ -62,9 +155,20 @@ pub struct Json2Splunk {
     pub vrl_dir: Option<PathBuf>,
     pub normalize_test_dir: Option<PathBuf>,
     pub input_type: Option<String>,
+    pub dlq_dir: PathBuf,
+    /// Throughput caps shared by all the HEC workers (None = unlimited)
+    pub max_eps: Option<f64>,
+    pub max_mbps: Option<f64>,
+    /// Indexes resolved from the patterns' index templates, created before ingestion
+    pub routed_indexes: Vec<String>,
+    /// Checkpoint journal used to resume an interrupted ingestion (Splunk mode only)
+    pub checkpoint: Option<Arc<CheckpointJournal>>,
+    /// Management API, for searches (set when the configuration has host and user)
+    splunk: Option<SplunkHelper>,
     client: Client, 
 }
 
+#[derive(Clone, Copy)]
 enum ParseMode {
     Json,
     Raw,
@@ -76,14 +180,139 @@ enum NormalizeWriter {
     Enabled(BufWriter<File>),
 }
 
+/// Unit of work scheduled on the pool: a file, or all the matched members of an archive.
+enum IngestUnit<'a> {
+    File(&'a FileTuple),
+    Archive(&'a Path, ArchiveKind, Vec<&'a FileTuple>),
+}
+
+/// One HEC collector per pool thread: the events of every file processed by a
+/// thread share its batches and its ack channel.
+struct WorkerCollectors {
+    collectors: Vec<Mutex<HttpEventCollector>>,
+}
+
+impl WorkerCollectors {
+    fn new(template: &HttpEventCollector, nb_threads: usize) -> Self {
+        WorkerCollectors {
+            collectors: (0..nb_threads.max(1))
+                .map(|_| Mutex::new(template.fork()))
+                .collect(),
+        }
+    }
+
+    /// Run `f` with the collector of the current pool thread.
+    fn with_current<R>(&self, f: impl FnOnce(&mut HttpEventCollector) -> R) -> R {
+        let idx = rayon::current_thread_index().unwrap_or(0) % self.collectors.len();
+        let mut hec = self.collectors[idx].lock().unwrap();
+        f(&mut hec)
+    }
+
+    /// Flush every collector and wait for its pending acks.
+    fn finish(self) {
+        self.collectors
+            .into_par_iter()
+            .for_each(|c| c.into_inner().unwrap().finish());
+    }
+}
+
+/// Per-file state shared by the pool threads processing the records of a file.
+struct FileJob<'a> {
+    label: &'a str,
+    path: &'a Path,
+    ctx: EventContext,
+    sourcefile: Arc<str>,
+    vrl_chain: VrlChain,
+    writer: Option<Mutex<BufWriter<File>>>,
+    progress: Option<Arc<FileProgress>>,
+    /// Number of events sent for this file
+    event_count: AtomicU64,
+    /// Lines already delivered by a previous run (--resume)
+    skip_lines: usize,
+}
+
+impl FileJob<'_> {
+    /// A line handled without producing an event (empty, invalid, dropped by VRL).
+    fn skip(&self, line: usize) {
+        if let Some(ref p) = self.progress {
+            p.complete(line);
+        }
+    }
+
+    /// Called by the reader at EOF with the number of the last line read.
+    fn finish_reading(&self, total_lines: usize) {
+        if let Some(ref p) = self.progress {
+            p.finish_reading(total_lines);
+        }
+    }
+
+    /// Normalize a record, then write it to the normalize-test output and/or batch it to HEC.
+    fn emit(&self, mut record: Value, line: usize, collectors: Option<&WorkerCollectors>) {
+        // 1. Apply VRL normalization chain (if configured), with the file metadata in %
+        let mut overrides = None;
+        if !self.vrl_chain.is_empty() {
+            let ctx = &self.ctx;
+            let metadata = event_metadata(&ctx.sourcefile, line, &ctx.source, &ctx.artifact, &ctx.host_base);
+            match apply_vrl_chain_to_record(record, &self.vrl_chain, metadata) {
+                Some((norm, splunk)) => {
+                    record = norm;
+                    overrides = splunk;
+                }
+                None => {
+                    // Record dropped by VRL
+                    self.skip(line);
+                    return;
+                }
+            }
+        }
+
+        // 2. If normalize-test mode → write normalized JSON
+        if let Some(ref writer) = self.writer
+            && let Ok(bytes) = serde_json::to_vec(&record)
+        {
+            let mut w = writer.lock().unwrap();
+            if w.write_all(&bytes).and_then(|_| w.write_all(b"\n")).is_err() {
+                warn!("Failed to write normalized record of {:?}", self.path);
+            }
+        }
+
+        // 3. If Splunk HEC is enabled → build payload and batch it on this thread's collector
+        if let Some(collectors) = collectors {
+            let payload = Json2Splunk::build_payload(record, &self.ctx, overrides.as_ref());
+            let origin = EventOrigin::new(Arc::clone(&self.sourcefile), line, self.progress.clone());
+            collectors.with_current(|hec| hec.batch_event(payload, origin));
+            // Increment the total event counter whenever an event is sent
+            self.event_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Log completion and, if HEC is configured, queue a summary event with the expected count.
+    fn finish(self, collectors: Option<&WorkerCollectors>) {
+        if let Some(writer) = self.writer {
+            let _ = writer.into_inner().unwrap().flush();
+        }
+
+        info!("Finished {} file {:?}", self.label, self.path);
+
+        if let Some(collectors) = collectors {
+            let total = self.event_count.load(Ordering::Relaxed);
+            let mut summary_record = json!({
+                "expected_event_count": total,
+                "event_type": "ingestion_metadata",
+            });
+            if self.skip_lines > 0 {
+                summary_record["resumed_from_line"] = json!(self.skip_lines);
+            }
+            let payload = Json2Splunk::build_payload(summary_record, &self.ctx, None);
+            let origin = EventOrigin::new(self.sourcefile, 0, None);
+            collectors.with_current(|hec| hec.batch_event(payload, origin));
+        }
+    }
+}
+
 impl Json2Splunk {
     pub fn new(normalize_test_dir: Option<PathBuf>) -> Self {
-            let client = Client::builder()
-                .timeout(std::time::Duration::from_secs(15))
-                .danger_accept_invalid_certs(true)
-                .pool_idle_timeout(std::time::Duration::from_secs(90))
-                .pool_max_idle_per_host(32)
-                .build()
+            let client = Self::build_client(&TlsSettings::default())
                 .expect("Failed to create global HTTP client");
 
             Json2Splunk {
@@ -94,386 +323,243 @@ impl Json2Splunk {
                 normalize_test_dir,
                 vrl_dir: None,
                 input_type: None,
+                dlq_dir: PathBuf::from("json2splunk_dlq"),
+                max_eps: None,
+                max_mbps: None,
+                routed_indexes: Vec::new(),
+                checkpoint: None,
+                splunk: None,
                 client, 
             }
         }
 
-    pub fn set_vrl_dir(&mut self, dir: Option<PathBuf>) {
-        self.vrl_dir = dir;
+    /// Where single events refused by HEC are quarantined, apart from the failed batches.
+    fn quarantine_dir(&self) -> PathBuf {
+        self.dlq_dir.join("quarantine")
     }
 
-    fn init_hec(hec_template_opt: Option<&HttpEventCollector>, is_normalize: bool, file_kind_label: &str,) -> Option<Option<HttpEventCollector>> {
-        if is_normalize {
-            return Some(None);
-        }
+    /// Shared HTTP client of the management API and HEC, with the TLS settings of the Splunk configuration.
+    fn build_client(tls: &TlsSettings) -> Result<Client, String> {
+        let builder = Client::builder()
+            .timeout(std::time::Duration::from_secs(15))
+            .pool_idle_timeout(std::time::Duration::from_secs(90))
+            .pool_max_idle_per_host(32);
 
-        match hec_template_opt {
-            Some(h) => Some(Some(h.clone())),
-            None => {
-                error!(
-                    "HEC template is required for {} processing in Splunk mode.",
-                    file_kind_label
-                );
-                None
-            }
-        }
+        tls.apply(builder)?
+            .build()
+            .map_err(|e| describe_error(&e))
     }
-    fn run_parallel_line_pipeline(&self, 
-        file_kind_label: &str,
+
+    pub fn set_vrl_dir(&mut self, dir: Option<PathBuf>) {
+        self.vrl_dir = dir;
+    }
+
+    /// Open everything a file needs before its records are scheduled on the pool:
+    /// normalize-test writer, event context and VRL chain.
+    /// Returns None when the file must be skipped.
+    fn open_job<'a>(
+        &self,
+        file_kind_label: &'a str,
         default_output_name: &str,
-        hec_template_opt: Option<&HttpEventCollector>,
-        file_tuples: &FileTuple,
+        file_tuples: &'a FileTuple,
         normalize_dir: Option<&PathBuf>,
-        mode: ParseMode,
-    ) {
-        // 1. Resolve the input file path and check if we are in normalize-test mode
-        let path = file_tuples.file_path.clone();
-        let is_normalize = normalize_dir.is_some();
-    
-        // 2. Initialize the HEC template (only required if we actually send to Splunk)
-        //    - If normalize mode is enabled, this is skipped internally
-        //    - If HEC is required but missing, we abort processing
-        let hec_template = match Self::init_hec(hec_template_opt, is_normalize, file_kind_label) {
-            Some(h) => h,
-            None => return,
-        };
-    
-        // 3. Initialize the normalize writer (only used when normalize-test is enabled)
+        progress: Option<Arc<FileProgress>>,
+    ) -> Option<FileJob<'a>> {
+        let path = &file_tuples.file_path;
+
+        // 1. Initialize the normalize writer (only used when normalize-test is enabled)
         //    This function:
         //      - Creates <input>.<hash>.normalized.jsonl
         //      - Updates normalize_mapping.json
         //      - Returns a NormalizeWriter enum describing what to do
-        let writer_state = Self::init_normalize_writer(
+        let writer = match Self::init_normalize_writer(
             normalize_dir,
-            &path,
+            path,
             default_output_name,
             file_kind_label,
             &file_tuples.source,
-        );
-    
-        // 4. Build the shared event context (metadata: source, sourcetype, host, etc.)
-        let ctx = Arc::new(Self::build_event_context(file_tuples, &path));
-        
-        // Create an atomic counter to count the number of events sent for this file.
-        let event_count = Arc::new(AtomicU64::new(0));
-
-        // 5. Compile the VRL normalization chain for this file type
-        let vrl_chain: Arc<VrlChain> =
-            Arc::new(compile_vrl_chain(self.vrl_dir.as_deref(), &file_tuples.normalize));
-    
-        // 6. Open the input file for reading
-        let file = match File::open(&path) {
-            Ok(f) => f,
-            Err(e) => {
-                error!("Failed to open {} file {:?}: {}", file_kind_label, path, e);
-                return;
-            }
+        ) {
+            // Fatal error during writer initialization → abort this file
+            NormalizeWriter::Abort => return None,
+            NormalizeWriter::Disabled => None,
+            NormalizeWriter::Enabled(w) => Some(Mutex::new(w)),
         };
-    
-        // 7. Create channel for normalized-line writing (used only in normalize-test mode)
-        let (write_tx, write_rx) = chan::bounded::<Vec<u8>>(10_000);
-        let mut writer_handle = None;
-    
-        // 8. Decide how to handle normalized output based on NormalizeWriter
-        match writer_state {
-            // 8.1 Fatal error during writer initialization → abort this file
-            NormalizeWriter::Abort => {
-                drop(write_rx);
-                return;
-            }
-    
-            // 8.2 Normalize-test disabled → drop receiver so worker threads won’t block
-            NormalizeWriter::Disabled => {
-                drop(write_rx);
-            }
-    
-            // 8.3 Normalize-test enabled → spawn writer thread
-            NormalizeWriter::Enabled(mut w) => {
-                writer_handle = Some(thread::spawn(move || {
-                    // This thread receives serialized JSON records and writes them
-                    // to <input>.<hash>.normalized.jsonl
-                    for bytes in write_rx {
-                        if w.write_all(&bytes).is_err() || w.write_all(b"\n").is_err() {
-                            warn!("Failed to write to output file, stopping writer thread");
-                            break;
-                        }
-                    }
-                    let _ = w.flush();
-                }));
-            }
-        }
-    
-        // 9. Create channel between the reader thread and worker threads
-        let (tx, rx) = chan::bounded::<(usize, String)>(10_000);
-    
-        // 10. Determine how many worker threads to use: max available, minimum 1
-        let nb_workers = self.nb_cpu.max(1);
-
-        // Check if encoding is set in indexer patterns
-        let is_utf8 = if let Some(enc) = &file_tuples.encoding {
-            if enc.eq_ignore_ascii_case("utf-8") || enc.eq_ignore_ascii_case("utf8") {
-                info!("{} encoding '{}' detected for {:?}, using fast UTF-8 reader.", file_kind_label, enc, path);
-                true
-            } else {
-                warn!("Encoding '{}' for {:?} not supported (only UTF-8). Falling back to lossy reader.", enc, path);
-                false
-            }
-        } else {
-            // No encoding hint → assume non-UTF8 and be safe
-            warn!("No encoding metadata for {:?}, falling back to lossy reader.", path);
-            false
+
+        // 2. Build the shared event context (metadata: source, sourcetype, host, etc.)
+        let ctx = Self::build_event_context(file_tuples, path);
+        let sourcefile: Arc<str> = Arc::from(ctx.sourcefile.as_str());
+
+        // 3. Compile the VRL normalization chain for this file type (cached per script)
+        let vrl_chain = compile_vrl_chain(self.vrl_dir.as_deref(), &file_tuples.normalize);
+
+        // 4. Lines (or CSV rows) already delivered by a previous run (--resume)
+        let skip_lines = progress.as_ref().map(|p| p.start_line()).unwrap_or(0);
+        if skip_lines > 0 {
+            info!("Resuming {:?} after line {}", path, skip_lines);
+        }
+
+        Some(FileJob {
+            label: file_kind_label,
+            path,
+            ctx,
+            sourcefile,
+            vrl_chain,
+            writer,
+            progress,
+            event_count: AtomicU64::new(0),
+            skip_lines,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_parallel_line_pipeline(
+        &self,
+        file_kind_label: &str,
+        default_output_name: &str,
+        collectors: Option<&WorkerCollectors>,
+        file_tuples: &FileTuple,
+        normalize_dir: Option<&PathBuf>,
+        mode: ParseMode,
+        progress: Option<Arc<FileProgress>>,
+        input: Box<dyn Read + Send>,
+    ) {
+        // 1. Prepare the per-file state shared by the pool threads
+        let job = match self.open_job(file_kind_label, default_output_name, file_tuples, normalize_dir, progress) {
+            Some(j) => j,
+            None => return,
         };
 
+        // 2. Decode the input to UTF-8 according to the `encoding` pattern field
+        let input = decoded_input(input, file_tuples.encoding.as_deref(), job.path, file_kind_label);
+
+        // 3. Line source: reads the file sequentially and yields (line number, content).
+        //    Lines are pulled by the pool threads through par_bridge, so the records of
+        //    this file are processed concurrently with the records of other files.
+        let mut reader = BufReader::new(input);
+        let mut buf = Vec::<u8>::new();
+        let mut lineno = 0usize;
+        let job_ref = &job;
+
+        // Raw files with a line merge rule keep their indentation (continuation lines)
+        let line_merge = match mode {
+            ParseMode::Raw => file_tuples.line_merge.clone(),
+            ParseMode::Json => None,
+        };
+        let keep_indent = line_merge.is_some();
 
-        // 11. Spawn the reader thread (single producer)
-        let path_for_reader = path.clone();
-        let reader_handle = thread::spawn(move || {
-            let mut reader = BufReader::new(file);
-            let mut buf = Vec::<u8>::new();
-            let mut lineno = 0usize;
-    
-            loop {
-                buf.clear();
-    
-                // 11.1 Read one line from the input file
-                let res = reader.read_until(b'\n', &mut buf);
-                match res {
-                    Ok(0) => break, // EOF
-                    Ok(_) => {}
-                    Err(e) => {
-                        error!("Error reading {:?}: {}", path_for_reader, e);
-                        break;
-                    }
-                };
-    
-                lineno += 1;
-    
-                // 11.2 Convert raw bytes to trimmed string
-                let raw = if is_utf8 {
-                    // Fast path: avoid lossy and avoid a second allocation
-                    match std::str::from_utf8(&buf) {
-                        Ok(s) => s.trim().to_string(),
-                        Err(_) => {
-                            // fallback to lossy if needed
-                            String::from_utf8_lossy(&buf).trim().to_string()
-                        }
-                    }
-                } else {
-                    // Non-UTF8 declared encoding → decode lossy immediately
-                    String::from_utf8_lossy(&buf).trim().to_string()
-                };
-    
-                // Skip empty lines
-                if raw.is_empty() {
-                    continue;
+        let lines = std::iter::from_fn(move || loop {
+            buf.clear();
+
+            // 3.1 Read one line from the input file
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) => {
+                    // Only a complete read can mark the file as done in the checkpoint journal
+                    job_ref.finish_reading(lineno);
+                    return None;
                 }
-    
-                // 11.3 Send line number + content to worker pool
-                if tx.send((lineno, raw)).is_err() {
-                    break;
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Error reading {:?}: {}", job_ref.path, e);
+                    return None;
                 }
-            }
-    
-            debug!("Reader thread finished for {:?}", path_for_reader);
-        });
-    
-        // 12. Spawn worker threads (parallel consumers)
-        let mut worker_handles = Vec::with_capacity(nb_workers);
-    
-        for _ in 0..nb_workers {
-            let rx = rx.clone();
-            let ctx = Arc::clone(&ctx);
-            let vrl_chain = Arc::clone(&vrl_chain);
-            let event_count = Arc::clone(&event_count);
-
-            // 12.1 Only clone write channel if writer thread exists
-            let write_tx = if writer_handle.is_some() {
-                Some(write_tx.clone())
-            } else {
-                None
             };
-    
-            // 12.2 Each worker gets its own cloned HEC client
-            let mut hec_client = hec_template.clone();
-            let path_for_worker = path.clone();
-    
-            // 12.3 Copy parsing mode to avoid sharing reference
-            let mode_for_worker = match mode {
-                ParseMode::Json => ParseMode::Json,
-                ParseMode::Raw => ParseMode::Raw,
+
+            lineno += 1;
+
+            // Already sent by a previous run
+            if lineno <= job_ref.skip_lines {
+                continue;
+            }
+
+            // 3.2 Convert raw bytes to trimmed string
+            //     The input is already decoded: lossy only for invalid bytes of a declared UTF-8 file
+            let text = match std::str::from_utf8(&buf) {
+                Ok(s) => Cow::Borrowed(s),
+                Err(_) => String::from_utf8_lossy(&buf),
             };
-    
-            worker_handles.push(thread::spawn(move || {
-                let mut local_count = 0usize;
-    
-                for (lineno, raw) in rx.iter() {
-    
-                    // 13. Parse input line depending on file mode
-                    let mut record = match mode_for_worker {
-    
-                        // 13.1 JSON mode → parse as JSON object
-                        ParseMode::Json => {
-                            match serde_json::from_str::<Value>(&raw) {
-                                Ok(value) => value,
-                                Err(e) => {
-                                    warn!(
-                                        "Invalid JSON at line {} in {:?}: {}. Skipping.",
-                                        lineno, path_for_worker, e
-                                    );
-                                    continue;
-                                }
-                            }
-                        }
-    
-                        // 13.2 RAW mode → wrap raw content into { "message": "..." }
-                        ParseMode::Raw => {
-                            let trimmed = raw.trim();
-                            if trimmed.is_empty() {
-                                continue;
-                            }
-                            json!({ "message": trimmed })
-                        }
-                    };
-    
-                    // 14. Apply VRL normalization chain (if configured)
-                    if !vrl_chain.is_empty() {
-                        match apply_vrl_chain_to_record(record, &vrl_chain) {
-                            Some(norm) => record = norm,
-                            None => continue, // Record dropped by VRL
-                        }
-                    }
-    
-                    // 15. If normalize-test mode → send normalized JSON to writer thread
-                    if let Some(ref tx) = write_tx {
-                        if let Ok(bytes) = serde_json::to_vec(&record) {
-                            let _ = tx.send(bytes);
-                        }
-                    }
-    
-                    // 16. If Splunk HEC is enabled → build payload and batch-send
-                    if let Some(ref mut hec) = hec_client {
-                        let payload = Json2Splunk::build_payload(record, &ctx);
-                        hec.batch_event(payload);
-                            // Increment the total event counter whenever an event is sent
-                            event_count.fetch_add(1, Ordering::Relaxed);
-                    }
+            let raw = if keep_indent { text.trim_end() } else { text.trim() }.to_string();
+
+            // Skip empty lines
+            if raw.trim_start().is_empty() {
+                job_ref.skip(lineno);
+                continue;
+            }
+
+            return Some((lineno, raw));
+        });
+
+        // 4. Merge the lines of multiline raw events (stack traces, transcripts...).
+        //    An event is numbered after its first line; continuation lines are completed
+        //    at once, so --resume always restarts at the start of an event.
+        let events: Box<dyn Iterator<Item = (usize, String)> + Send + '_> = match line_merge {
+            Some(rule) => Box::new(merge_lines(lines, rule).map(move |event| {
+                for line in event.first_line + 1..=event.last_line {
+                    job_ref.skip(line);
                 }
-    
-                // 17. Final flush for events still buffered
-                if let Some(mut hec) = hec_client {
-                    hec.flush_batch();
-                }
-            }));
-        }
-    
-        // 18. Drop the main write sender so writer thread terminates once workers finish
-        drop(write_tx);
-    
-        // 19. Wait for reader thread to finish
-        let _ = reader_handle.join();
-    
-        // 20. Wait for all worker threads to finish
-        for h in worker_handles {
-            let _ = h.join();
-        }
-    
-        // 21. Wait for writer thread to finish (if it exists)
-        if let Some(h) = writer_handle {
-            let _ = h.join();
-        }
-    
-        // 22. Log completion
-        info!("Finished {} file {:?}", file_kind_label, path);
-
-        // If Splunk HEC is configured, send a summary event with the expected count.
-        if let Some(ref hec_template) = hec_template {
-            let total = event_count.load(Ordering::Relaxed);
-            let summary_record = json!({
-                "expected_event_count": total,
-                "event_type": "ingestion_metadata",
-            });
-            let payload = Json2Splunk::build_payload(summary_record, &ctx);
-            let mut hec = hec_template.clone();
-            hec.batch_event(payload);
-            hec.flush_batch();
-        }
+                (event.first_line, event.text)
+            })),
+            None => Box::new(lines),
+        };
+
+        // 5. Process events on the pool
+        events.par_bridge().for_each(|(lineno, raw)| {
+            // 5.1 Parse input line depending on file mode
+            let record = match mode {
+                // JSON mode → parse as JSON object
+                ParseMode::Json => match serde_json::from_str::<Value>(&raw) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        warn!(
+                            "Invalid JSON at line {} in {:?}: {}. Skipping.",
+                            lineno, job.path, e
+                        );
+                        job.skip(lineno);
+                        return;
+                    }
+                },
+
+                // RAW mode → wrap raw content into { "message": "..." },
+                // plus the named captures of extract_rex
+                ParseMode::Raw => raw_record(raw, file_tuples.extract_re.as_ref()),
+            };
+
+            // 5.2 VRL, normalize-test output and HEC batching
+            job.emit(record, lineno, collectors);
+        });
+
+        // 6. Log completion and queue the summary event
+        job.finish(collectors);
     }
-    
 
     fn run_parallel_csv_pipeline(
         &self,
-        hec_template_opt: Option<&HttpEventCollector>,
+        collectors: Option<&WorkerCollectors>,
         file_tuples: &FileTuple,
         normalize_dir: Option<&PathBuf>,
+        progress: Option<Arc<FileProgress>>,
+        input: Box<dyn Read + Send>,
     ) {
-        // 1. Resolve input CSV path and check if we are in normalize-test mode
-        let path = file_tuples.file_path.clone();
-        let is_normalize = normalize_dir.is_some();
-    
-        // 2. Initialize HEC template (if needed)
-        //    - If normalize-test is enabled, HEC may be skipped internally
-        //    - If HEC is required but not available, abort this file
-        let hec_template = match Self::init_hec(hec_template_opt, is_normalize, "CSV") {
-            Some(h) => h,
+        // 1. Prepare the per-file state shared by the pool threads
+        let job = match self.open_job("CSV", "output.csv", file_tuples, normalize_dir, progress) {
+            Some(j) => j,
             None => return,
         };
-    
-        // 3. Initialize normalize writer (only meaningful in normalize-test mode)
-        //    This may:
-        //      - Create <input>.<hash>.normalized.jsonl
-        //      - Update normalize_mapping.json
-        //      - Return a NormalizeWriter enum describing the outcome
-        let writer_state = Self::init_normalize_writer(
-            normalize_dir,
-            &path,
-            "output.csv",
-            "CSV",
-            &file_tuples.source,
-        );
-        
-        // 4. Build shared event context (host/source/sourcetype/artifact...)
-        let ctx = Arc::new(Self::build_event_context(file_tuples, &path));
-    
-        // Create an atomic counter to count the number of events sent for this file.
-        let event_count = Arc::new(AtomicU64::new(0));
-
-        // 5. Compile VRL normalization chain for this CSV file (if any)
-        let vrl_chain: Arc<VrlChain> =
-            Arc::new(compile_vrl_chain(self.vrl_dir.as_deref(), &file_tuples.normalize));
-    
-        // 6. Open CSV input file
-        let file = match File::open(&path) {
-            Ok(f) => f,
-            Err(e) => {
-                error!("Failed to open CSV file {:?}: {}", path, e);
-                return;
-            }
-        };
-    
-        // 7. Wrap file in a BufReader → LossyUtf8Reader → CSV reader
-        //    - LossyUtf8Reader handles invalid UTF-8 gracefully
+        let path = &file_tuples.file_path;
+
+        // 2. Wrap input in a decoder → CSV reader
+        //    - the decoder converts the `encoding` of the pattern to UTF-8
+        //      (LossyUtf8Reader when no encoding is declared)
         //    - CSV reader is configured to:
         //        * Treat first row as headers
         //        * Allow variable-length rows (flexible)
-        let buf_reader = BufReader::new(file);
-        let reader: Box<dyn Read + Send> = if let Some(enc) = &file_tuples.encoding {
-            if enc.eq_ignore_ascii_case("utf-8") || enc.eq_ignore_ascii_case("utf8") {
-                info!("CSV encoding '{}' detected for {:?}, using fast UTF-8 reader.", enc, path);
-                Box::new(buf_reader)
-            } else {
-                warn!("Encoding '{}' for {:?} not supported (only UTF-8). Falling back to lossy reader.", enc, path);
-                Box::new(LossyUtf8Reader::new(buf_reader))
-            }
-        } else {
-            Box::new(LossyUtf8Reader::new(buf_reader))
-        };
+        let reader = decoded_input(input, file_tuples.encoding.as_deref(), path, "CSV");
 
         let mut rdr = csv::ReaderBuilder::new()
             .has_headers(true)
             .flexible(true)
             .from_reader(reader);
 
-        // 8. Read CSV headers into a separate structure for mapping to JSON keys
+        // 3. Read CSV headers into a separate structure for mapping to JSON keys
         let headers = match rdr.headers() {
             Ok(h) => h.clone(),
             Err(e) => {
@@ -481,205 +567,133 @@ impl Json2Splunk {
                 return;
             }
         };
-    
-        // 9. Create channel for normalized JSONL writing (used in normalize-test mode)
-        let (write_tx, write_rx) = chan::bounded::<Vec<u8>>(10_000);
-        let mut writer_handle = None;
-        
-        // 10. Handle normalize writer behavior based on NormalizeWriter state
-        match writer_state {
-            // 10.1 Irrecoverable error during writer setup → abort processing
-            NormalizeWriter::Abort => {
-                drop(write_rx);
-                return;
-            }
-            // 10.2 Normalize-test disabled → drop receiver so no writer thread is used
-            NormalizeWriter::Disabled => {
-                // No normalize-test output; drop receiver so workers won't block on send
-                drop(write_rx);
-            }
-            // 10.3 Normalize-test enabled → spawn writer thread to consume write_rx
-            NormalizeWriter::Enabled(mut w) => {
-                writer_handle = Some(thread::spawn(move || {
-                    let mut lines: u64 = 0;
-
-                    // This thread writes each serialized JSON line to the normalized output file
-                    for bytes in write_rx {
-                        lines += 1;
-                        if w.write_all(&bytes).is_err() || w.write_all(b"\n").is_err() {
-                            break;
-                        }
-                    }
-                    let _ = w.flush();
-                }));
-            }
-        }
-        
-        // 11. Create channel from CSV reader → worker threads
-        //     - Each message is a raw CSV record (StringRecord)
-        let (tx, rx) = chan::bounded::<csv::StringRecord>(10_000);
-    
-        // 12. Decide number of worker threads
-        let nb_workers = self.nb_cpu.max(1);
-    
-        // 13. Spawn reader thread that:
-        //      - Iterates over CSV records
-        //      - Sends raw CSV records to worker threads via tx
-        let path_for_reader = path.clone();
-        let reader_handle = thread::spawn(move || {
-            let mut rows: usize = 0;
-
-            // Explicit iterator so we can time the "next" call
-            let mut iter = rdr.records();
-            let path_for_reader2 = path_for_reader.clone();
-
-            loop {
-                let result_opt = iter.next();
-
-                let result = match result_opt {
-                    Some(res) => res,
-                    None => break, // EOF
-                };
 
-                let record = match result {
-                    Ok(r) => r,
-                    Err(e) => {
-                        warn!(
-                            "CSV row {} in {:?} could not be parsed: {}. Skipping.",
-                            rows + 1,
-                            path_for_reader2,
-                            e
-                        );
-                        continue;
-                    }
-                };
+        // 4. Row source: iterates over CSV records and yields (row number, record)
+        let mut records = rdr.into_records();
+        let mut rows: usize = 0;
+        let job_ref = &job;
 
-                rows += 1;
+        let rows_iter = std::iter::from_fn(move || loop {
+            let result = match records.next() {
+                Some(res) => res,
+                None => {
+                    job_ref.finish_reading(rows);
+                    return None; // EOF
+                }
+            };
+
+            // Rows are numbered including unparsable ones, so a resumed run skips the same rows
+            rows += 1;
+            if rows <= job_ref.skip_lines {
+                continue;
+            }
 
-                if tx.send(record).is_err() {
-                    break;
+            match result {
+                Ok(r) => return Some((rows, r)),
+                Err(e) => {
+                    warn!(
+                        "CSV row {} in {:?} could not be parsed: {}. Skipping.",
+                        rows,
+                        job_ref.path,
+                        e
+                    );
+                    job_ref.skip(rows);
                 }
             }
         });
 
-    
-        // 14. Spawn worker threads to process records in parallel
-        let mut worker_handles = Vec::with_capacity(nb_workers);
-    
-        for worker_id in 0..nb_workers {
-            let rx = rx.clone();
-            let ctx = Arc::clone(&ctx);
-            let vrl_chain = Arc::clone(&vrl_chain);
-            let headers = headers.clone(); // each worker gets its own copy of headers
-            let event_count = Arc::clone(&event_count);
-
-            // 14.1 Only create a write channel handle if writer thread exists
-            let write_tx = if writer_handle.is_some() {
-                Some(write_tx.clone())
-            } else {
-                None
-            };
-    
-            // 14.2 Each worker gets its own HEC client clone (if HEC is enabled)
-            let mut hec_client = hec_template.clone();
-    
-            worker_handles.push(thread::spawn(move || {
-
-                let mut local_count = 0usize;
-    
-                // 15. Consume CSV records from the channel
-                for record in rx.iter() {
-
-                    // 15.0 Build JSON object from headers + record in the worker
-                    let mut map = Map::new();
-                    let mut all_empty = true;
-                    for (h, v) in headers.iter().zip(record.iter()) {
-                        let s = v.to_string();
-                        if !s.is_empty() {
-                            all_empty = false;
-                        }
-                        map.insert(h.to_string(), Value::String(s));
-                    }
+        // 5. Process rows on the pool
+        rows_iter.par_bridge().for_each(|(row, record)| {
+            // 5.1 Build JSON object from headers + record
+            let mut map = Map::new();
+            let mut all_empty = true;
+            for (h, v) in headers.iter().zip(record.iter()) {
+                let s = v.to_string();
+                if !s.is_empty() {
+                    all_empty = false;
+                }
+                map.insert(h.to_string(), Value::String(s));
+            }
 
-                    // Skip rows where all fields are empty
-                    if all_empty {
-                        continue;
-                    }
+            // Skip rows where all fields are empty
+            if all_empty {
+                job.skip(row);
+                return;
+            }
 
-                    let mut record_val = Value::Object(map);
-    
-                    // 15.1 Apply VRL normalization chain (if not empty)
-                    if !vrl_chain.is_empty() {
-                        let res = apply_vrl_chain_to_record(record_val, &vrl_chain);
+            // 5.2 VRL, normalize-test output and HEC batching
+            job.emit(Value::Object(map), row, collectors);
+        });
 
-                        match res {
-                            Some(norm) => record_val = norm,
-                            None => continue, // record dropped by VRL logic
-                        }
-                    }
-    
-                    // 15.2 If normalize-test mode: forward normalized JSON to writer thread
-                    if let Some(ref tx) = write_tx {
-                        if let Ok(bytes) = serde_json::to_vec(&record_val) {
-                            let _ = tx.send(bytes);
-                        }
-                    }
-    
-                    // 15.3 If Splunk HEC is configured: send record as a batched event
-                    if let Some(ref mut hec) = hec_client {
-                        let payload = Json2Splunk::build_payload(record_val, &ctx);
-                        hec.batch_event(payload);
-                        local_count += 1;
-                        // Increment the overall event counter
-                        event_count.fetch_add(1, Ordering::Relaxed);
-                        // 15.4 Periodically flush batches (every 1000 events)
-                        if local_count % 1000 == 0 {
-                            hec.flush_batch();
-                        }
-                    }
-                }
-    
-                // 16. Final flush of any remaining batched events
-                if let Some(mut hec) = hec_client {
-                    hec.flush_batch();
-                }
-            }));
-        }
-    
-        // 17. Drop the main write sender so that, once workers finish,
-        //     the writer thread sees channel closure and exits cleanly
-        drop(write_tx); 
-    
-        // 18. Wait for CSV reader thread to finish
-        let _ = reader_handle.join();
-    
-        // 19. Wait for all worker threads to finish
-        for h in worker_handles {
-            let _ = h.join();
-        }
-    
-        // 20. Wait for writer thread to finish (if normalize-test writer was spawned)
-        if let Some(h) = writer_handle {
-            let _ = h.join();
-        }
-    
-        info!("Finished CSV file {:?}", path);
-
-        // Emit a summary event with the expected number of events for this CSV file
-        if let Some(ref hec_template) = hec_template {
-            let total = event_count.load(Ordering::Relaxed);
-            let summary_record = json!({
-                "expected_event_count": total,
-                "event_type": "ingestion_metadata",
-            });
-            let payload = Json2Splunk::build_payload(summary_record, &ctx);
-            let mut hec = hec_template.clone();
-            hec.batch_event(payload);
-            hec.flush_batch();
-        }
+        // 6. Log completion and queue the summary event
+        job.finish(collectors);
     }
 
 
+    fn run_evtx_pipeline(
+        &self,
+        collectors: Option<&WorkerCollectors>,
+        file_tuples: &FileTuple,
+        normalize_dir: Option<&PathBuf>,
+        progress: Option<Arc<FileProgress>>,
+        input: Box<dyn Read + Send>,
+    ) {
+        // 1. Prepare the per-file state shared by the pool threads
+        let job = match self.open_job("EVTX", "output.evtx", file_tuples, normalize_dir, progress) {
+            Some(j) => j,
+            None => return,
+        };
+
+        // 2. Open the EVTX parser (file header + chunk count)
+        let mut parser = match open_evtx(job.path, file_tuples.archive.is_some(), input) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Failed to open EVTX file {:?}: {}", job.path, e);
+                return;
+            }
+        };
+
+        // 3. Record source: parses the chunks sequentially and yields (record number, JSON).
+        //    Records are numbered in file order, including unparsable ones, for --resume.
+        let mut records = parser.records_json_value();
+        let mut recno: usize = 0;
+        let job_ref = &job;
+
+        let records_iter = std::iter::from_fn(move || loop {
+            let result = match records.next() {
+                Some(res) => res,
+                None => {
+                    job_ref.finish_reading(recno);
+                    return None; // EOF
+                }
+            };
+
+            recno += 1;
+            if recno <= job_ref.skip_lines {
+                continue;
+            }
+
+            match result {
+                Ok(record) => return Some((recno, record.data)),
+                Err(e) => {
+                    warn!(
+                        "EVTX record {} in {:?} could not be parsed: {}. Skipping.",
+                        recno, job_ref.path, e
+                    );
+                    job_ref.skip(recno);
+                }
+            }
+        });
+
+        // 4. Process records on the pool: VRL, normalize-test output and HEC batching
+        records_iter.par_bridge().for_each(|(recno, record)| {
+            job.emit(record, recno, collectors);
+        });
+
+        // 5. Log completion and queue the summary event
+        job.finish(collectors);
+    }
+
     fn init_normalize_writer(
         normalize_dir: Option<&PathBuf>,
         path: &Path,
@@ -735,6 +749,10 @@ impl Json2Splunk {
 
             let mapping_path = out_dir.join("normalize_mapping.json");
 
+            // Files are processed concurrently: serialize the read-modify-write of the mapping
+            static MAPPING_LOCK: Mutex<()> = Mutex::new(());
+            let _mapping_guard = MAPPING_LOCK.lock().unwrap();
+
             let mut mappings: Map<String, Value> = if mapping_path.exists() {
                 match File::open(&mapping_path) {
                     Ok(f) => {
@@ -817,6 +835,7 @@ impl Json2Splunk {
             timestamp_format: file_tuples.timestamp_format.clone(),
             artifact: file_tuples.artifact.clone(),
             sourcefile: path.to_string_lossy().to_string(),
+            index: file_tuples.index.clone(),
         }
     }
 
@@ -837,55 +856,332 @@ impl Json2Splunk {
                 warn!("Testing mode enabled. NO data will be sent to Splunk.");
             }
 
-            let file = match File::open(config_spl) {
-                Ok(f) => f,
-                Err(e) => {
-                    error!("Failed to open Splunk configuration file {:?}: {}", config_spl, e);
-                    return false;
-                }
+            let mut cfg = match Self::load_splunk_config(config_spl) {
+                Some(c) => c,
+                None => return false,
             };
 
-            let cfg: SplunkConfig = match serde_yaml::from_reader(file) {
+            if !Self::resolve_secrets(&mut cfg.splunk) {
+                return false;
+            }
+            let spl = &cfg.splunk;
+
+            // TLS settings apply to both the management API and HEC
+            self.client = match Self::build_client(&spl.tls) {
                 Ok(c) => c,
                 Err(e) => {
-                    error!("Failed to parse Splunk configuration YAML: {}", e);
+                    error!("Invalid TLS configuration: {}", e);
                     return false;
                 }
             };
+            // Direct HEC mode: an existing token, no management API nor admin credentials
+            let hec = match spl.hec_token {
+                Some(ref token) => self.direct_hec(spl, token.expose()),
+                None => self.managed_hec(spl, index),
+            };
+            let mut hec = match hec {
+                Some(h) => h,
+                None => return false,
+            };
 
-            let spl = &cfg.splunk;
-            info!("Splunk config: host={}, mport={}, ssl={}", spl.host, spl.mport, spl.ssl);
+            hec.index = Some(index.to_string());
+            hec.ssl_verify = spl.tls.verify();
 
-            let helper = SplunkHelper::new(&spl.host, &spl.user, &spl.password, spl.mport, spl.ssl);
+            let uses_tls = hec.endpoints.uses_tls() || (spl.hec_token.is_none() && spl.ssl);
+            if uses_tls && !hec.ssl_verify {
+                warn!("TLS certificate verification is disabled (verify: false): server certificates are not checked.");
+            }
+            hec.use_ack = spl.use_ack;
+            hec.ack_timeout = std::time::Duration::from_secs(spl.ack_timeout);
+            hec.retry = spl.retry.clone();
+            if spl.gzip {
+                if spl.gzip_level > 9 {
+                    error!("Invalid gzip_level {}: expected 0 to 9", spl.gzip_level);
+                    return false;
+                }
+                hec.gzip_level = Some(spl.gzip_level);
+            }
+            hec.dead_letters = Some(Arc::new(DeadLetterQueue::new(self.dlq_dir.clone())));
+            hec.quarantine = Some(Arc::new(DeadLetterQueue::new(self.quarantine_dir())));
+
+            if self.max_eps.is_some() || self.max_mbps.is_some() {
+                if let Some(bad) = [self.max_eps, self.max_mbps].into_iter().flatten().find(|v| !(*v > 0.0 && v.is_finite())) {
+                    error!("Invalid rate limit {}: --max-eps and --max-mbps must be positive", bad);
+                    return false;
+                }
+                info!(
+                    "HEC throughput capped at {} events/s, {} MB/s",
+                    self.max_eps.map_or("unlimited".to_string(), |v| v.to_string()),
+                    self.max_mbps.map_or("unlimited".to_string(), |v| v.to_string())
+                );
+                hec.rate_limiter = Some(Arc::new(RateLimiter::new(self.max_eps, self.max_mbps)));
+            }
 
-            if !helper.test_connection() {
-                error!("Unable to connect to Splunk management API.");
+            info!(
+                "HEC Instance Ready: endpoints={:?}, input={}, ssl_verify={}, gzip={:?}",
+                hec.endpoints.base_uris(),
+                hec.input_path(),
+                hec.ssl_verify,
+                hec.gzip_level
+            );
+            self.hec_template = Some(hec);
+            info!("Splunk configuration successful.");
+            true
+        }
+
+    /// Setup of verify mode: only the management API client, for searches. Unlike `configure`,
+    /// nothing is created or changed in Splunk (index, HEC input, useACK).
+    pub fn configure_verify(&mut self, config_spl: &Path) -> bool {
+        let mut cfg = match Self::load_splunk_config(config_spl) {
+            Some(c) => c,
+            None => return false,
+        };
+        // Searches always go through the management API, also with a direct HEC token
+        if !Self::resolve_password(&mut cfg.splunk) {
+            return false;
+        }
+        let spl = &cfg.splunk;
+
+        if spl.host.is_empty() || spl.user.is_empty() {
+            error!("Verify mode needs the management API: set host, user and password in the Splunk configuration.");
+            return false;
+        }
+
+        self.client = match Self::build_client(&spl.tls) {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Invalid TLS configuration: {}", e);
                 return false;
             }
+        };
+        if spl.ssl && !spl.tls.verify() {
+            warn!("TLS certificate verification is disabled (verify: false): server certificates are not checked.");
+        }
+
+        let helper = SplunkHelper::new(&spl.host, &spl.user, spl.password.expose(), spl.mport, spl.ssl, self.client.clone());
+        if !helper.test_connection() {
+            error!("Unable to connect to Splunk management API.");
+            return false;
+        }
+        self.splunk = Some(helper);
+        true
+    }
+
+    /// Read the Splunk configuration file. ${ENV_VAR} references are resolved before the
+    /// configuration is deserialized.
+    fn load_splunk_config(config_spl: &Path) -> Option<SplunkConfig> {
+        let file = match File::open(config_spl) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Failed to open Splunk configuration file {:?}: {}", config_spl, e);
+                return None;
+            }
+        };
+
+        let cfg: Result<SplunkConfig, String> = serde_yaml::from_reader::<_, serde_yaml::Value>(file)
+            .map_err(|e| e.to_string())
+            .and_then(|mut raw| interpolate_env(&mut raw).map(|_| raw))
+            .and_then(|raw| serde_yaml::from_value(raw).map_err(|e| e.to_string()));
+        match cfg {
+            Ok(c) => Some(c),
+            Err(e) => {
+                error!("Failed to parse Splunk configuration YAML: {}", e);
+                None
+            }
+        }
+    }
 
-            if !helper.create_index(index) {
-                error!("Failed to create or verify index {}", index);
+    /// Load the password and HEC token from `password_file` / `token_file`,
+    /// or prompt for the password (without echo) when none is configured and stdin is a TTY.
+    fn resolve_secrets(spl: &mut SplunkSection) -> bool {
+        if let Some(ref path) = spl.token_file {
+            match read_secret_file(path) {
+                Ok(token) => spl.hec_token = Some(token),
+                Err(e) => {
+                    error!("Cannot read token_file {:?}: {}", path, e);
+                    return false;
+                }
+            }
+        }
+
+        if let Some(ref token) = spl.hec_token {
+            if token.is_empty() {
+                error!("The HEC token is empty.");
                 return false;
             }
+            // Direct HEC mode: no password needed
+            return true;
+        }
+
+        Self::resolve_password(spl)
+    }
+
+    /// Load the password from `password_file`, or prompt for it when none is configured.
+    fn resolve_password(spl: &mut SplunkSection) -> bool {
+        if let Some(ref path) = spl.password_file {
+            match read_secret_file(path) {
+                Ok(password) => spl.password = password,
+                Err(e) => {
+                    error!("Cannot read password_file {:?}: {}", path, e);
+                    return false;
+                }
+            }
+        }
 
-            let token = match helper.ensure_hec_token(index) {
-                Some(t) => t,
+        if spl.password.is_empty() && !spl.user.is_empty() {
+            match prompt_secret(&format!("Splunk password for {}@{}: ", spl.user, spl.host)) {
+                Some(password) => spl.password = password,
                 None => {
-                    error!("Failed to obtain HEC token for index {}", index);
+                    error!("No Splunk password configured: set password or password_file, or run from a terminal to be prompted.");
                     return false;
                 }
-            };
+            }
+        }
 
-            let mut hec = HttpEventCollector::new(&token, &spl.host, "json", self.client.clone());
-            hec.index = Some(index.to_string());
+        true
+    }
 
-            info!("HEC Instance Ready: server_uri={}", hec.server_uri());
-            self.hec_template = Some(hec);
-            info!("Splunk configuration successful.");
-            true
+    /// Management API mode: create the index and ensure the json2splunk-rs HEC token.
+    fn managed_hec(&self, spl: &SplunkSection, index: &str) -> Option<HttpEventCollector> {
+        info!(
+            "Splunk config: host={}, mport={}, ssl={}, use_ack={}",
+            spl.host, spl.mport, spl.ssl, spl.use_ack
+        );
+
+        if spl.host.is_empty() || spl.user.is_empty() {
+            error!("Splunk configuration needs host, user and password (or hec_token for direct HEC mode).");
+            return None;
         }
 
-    fn build_payload(record: Value, ctx: &EventContext) -> Value {
+        let helper = SplunkHelper::new(&spl.host, &spl.user, spl.password.expose(), spl.mport, spl.ssl, self.client.clone());
+
+        if !helper.test_connection() {
+            error!("Unable to connect to Splunk management API.");
+            return None;
+        }
+
+        if let Err(e) = spl.index_settings.validate() {
+            error!("Invalid index_settings: {}", e);
+            return None;
+        }
+
+        if !helper.create_index(index, &spl.index_settings) {
+            error!("Failed to create or verify index {}", index);
+            return None;
+        }
+
+        let token = match helper.ensure_hec_token(index, spl.use_ack) {
+            Some(t) => t,
+            None => {
+                error!("Failed to obtain HEC token for index {}", index);
+                return None;
+            }
+        };
+
+        // Indexes of the patterns' index templates: all must be ready before ingestion starts
+        for routed in &self.routed_indexes {
+            if !helper.create_index(routed, &spl.index_settings) {
+                error!("Failed to create or verify index {}", routed);
+                return None;
+            }
+            if !helper.register_index_to_token(HEC_INPUT_NAME, routed) {
+                error!("Failed to associate index {} with HEC input {}", routed, HEC_INPUT_NAME);
+                return None;
+            }
+        }
+
+        let endpoints = self.hec_endpoints(spl, spl.hec_ssl.unwrap_or(spl.ssl))?;
+        Some(HttpEventCollector::new(&token, endpoints, "json", self.client.clone()))
+    }
+
+    /// Direct HEC mode: use the configured endpoint and token as-is.
+    /// The index must already exist and be allowed for the token.
+    fn direct_hec(&self, spl: &SplunkSection, token: &str) -> Option<HttpEventCollector> {
+        info!("Splunk config: direct HEC mode, use_ack={}", spl.use_ack);
+        if !spl.index_settings.params().is_empty() || spl.index_settings.app.is_some() {
+            warn!("Direct HEC mode: index_settings are ignored, indexes are not managed");
+        }
+        if !self.routed_indexes.is_empty() {
+            warn!(
+                "Direct HEC mode: indexes {:?} are not created; they must exist and be allowed for the token",
+                self.routed_indexes
+            );
+        }
+
+        // Host names without scheme: same scheme as in management API mode
+        let endpoints = self.hec_endpoints(spl, spl.hec_ssl.unwrap_or(spl.ssl))?;
+        let hec = HttpEventCollector::new(token, endpoints, "json", self.client.clone());
+
+        if !hec.check_health() {
+            error!("HEC is not ready to receive data.");
+            return None;
+        }
+
+        Some(hec)
+    }
+
+    /// HEC endpoints from `hec_url` (or `host`). `default_ssl` applies to entries without scheme.
+    fn hec_endpoints(&self, spl: &SplunkSection, default_ssl: bool) -> Option<EndpointPool> {
+        let urls = match spl.hec_url {
+            Some(ref urls) => urls.list(),
+            None => vec![spl.host.as_str()],
+        };
+
+        let mut endpoints = Vec::new();
+        for url in urls.iter().map(|u| u.trim()).filter(|u| !u.is_empty()) {
+            match Self::parse_hec_endpoint(url, default_ssl, spl.hec_port) {
+                Ok(endpoint) => endpoints.push(endpoint),
+                Err(e) => {
+                    error!("Invalid hec_url '{}': {}", url, e);
+                    return None;
+                }
+            }
+        }
+        if endpoints.is_empty() {
+            error!("No HEC endpoint: set hec_url (or host) in the Splunk configuration.");
+            return None;
+        }
+
+        let balance = match spl.hec_balance.as_deref() {
+            None => Balance::RoundRobin,
+            Some(name) => match Balance::from_name(name) {
+                Some(b) => b,
+                None => {
+                    error!("Invalid hec_balance '{}': expected round_robin or least_loaded", name);
+                    return None;
+                }
+            },
+        };
+        if endpoints.len() > 1 {
+            info!("{} HEC endpoints, balanced {:?}", endpoints.len(), balance);
+        }
+
+        Some(EndpointPool::new(endpoints, balance, self.client.clone()))
+    }
+
+    /// "https://hec.example.com:443", "http://10.0.0.1" or "hec.example.com".
+    fn parse_hec_endpoint(endpoint: &str, default_ssl: bool, default_port: Option<u16>) -> Result<HecEndpoint, String> {
+        let url = if endpoint.contains("://") {
+            endpoint.to_string()
+        } else {
+            format!("{}://{}", if default_ssl { "https" } else { "http" }, endpoint)
+        };
+        let url = reqwest::Url::parse(&url).map_err(|e| e.to_string())?;
+
+        let ssl = match url.scheme() {
+            "https" => true,
+            "http" => false,
+            other => return Err(format!("unsupported scheme '{}'", other)),
+        };
+        let host = url.host_str().ok_or("no host")?;
+        let port = url.port().or(default_port).unwrap_or(DEFAULT_HEC_PORT);
+
+        Ok(HecEndpoint::new(host, port, ssl))
+    }
+
+    /// HEC event of a record. `overrides` is the routing set by VRL in %splunk, which wins
+    /// over the pattern settings.
+    fn build_payload(record: Value, ctx: &EventContext, overrides: Option<&SplunkOverrides>) -> Value {
         let mut host = normalize_host(&ctx.host_base);
 
         if let Some(ref host_path_str) = ctx.host_path {
@@ -905,7 +1201,27 @@ impl Json2Splunk {
             }
         });
 
-        if !ctx.timestamp_paths.is_empty() {
+        if let Some(ref index) = ctx.index {
+            payload["index"] = json!(index);
+        }
+
+        // Routing set by VRL (%splunk.*)
+        let vrl_time = overrides.and_then(|o| o.time);
+        if let Some(o) = overrides {
+            for (key, value) in [("index", &o.index), ("sourcetype", &o.sourcetype), ("host", &o.host), ("source", &o.source)] {
+                if let Some(v) = value {
+                    payload[key] = json!(v);
+                }
+            }
+        }
+
+        if let Some(ts) = vrl_time {
+            if is_valid_hec_time(ts) {
+                payload["time"] = json!(ts);
+            } else {
+                debug!("Dropping unreasonable %splunk.time={} for sourcefile={}", ts, ctx.sourcefile);
+            }
+        } else if !ctx.timestamp_paths.is_empty() {
             let fmt_opt = if ctx.timestamp_format.is_empty() {
                 None
             } else {
@@ -972,34 +1288,342 @@ impl Json2Splunk {
             return;
         }
 
-        // Process each file
+        // Global worker pool: --nb_cpu threads shared by all files. Each thread keeps
+        // its own HEC collector for the whole run.
+        let pool = match rayon::ThreadPoolBuilder::new()
+            .num_threads(self.nb_cpu)
+            .thread_name(|i| format!("j2s-worker-{i}"))
+            .build()
+        {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Failed to build worker pool: {}", e);
+                return;
+            }
+        };
+        let collectors = hec_template
+            .as_ref()
+            .map(|h| WorkerCollectors::new(h, self.nb_cpu));
+
+        info!("Processing {} files with {} worker threads", tuples.len(), self.nb_cpu);
+
+        // Archive members are grouped per archive, so each archive is read only once
+        let mut units: Vec<IngestUnit> = Vec::new();
+        let mut archive_units: HashMap<&Path, usize> = HashMap::new();
         for file_tuples in tuples {
-            debug!(
-                "Starting ingestion of file {:?} for source {}",
-                file_tuples.file_path, file_tuples.source
-            );
-            self.process_file(hec_template.as_ref(), file_tuples, normalize_dir.as_ref(),
+            match file_tuples.archive {
+                Some(ref member) => {
+                    let idx = *archive_units.entry(&member.archive).or_insert_with(|| {
+                        units.push(IngestUnit::Archive(&member.archive, member.kind, Vec::new()));
+                        units.len() - 1
+                    });
+                    if let IngestUnit::Archive(_, _, ref mut members) = units[idx] {
+                        members.push(file_tuples);
+                    }
+                }
+                None => units.push(IngestUnit::File(file_tuples)),
+            }
+        }
+
+        let this = &*self;
+        pool.install(|| {
+            // Process files concurrently; the records of each file are spread over the pool too
+            units.par_iter().for_each(|unit| match unit {
+                IngestUnit::File(file_tuples) => {
+                    debug!(
+                        "Starting ingestion of file {:?} for source {}",
+                        file_tuples.file_path, file_tuples.source
+                    );
+                    this.process_file(collectors.as_ref(), file_tuples, normalize_dir.as_ref());
+                }
+                IngestUnit::Archive(archive, kind, members) => {
+                    this.process_archive(collectors.as_ref(), archive, *kind, members, normalize_dir.as_ref());
+                }
+            });
+
+            // Flush what is still buffered and wait for pending acks
+            if let Some(c) = collectors {
+                c.finish();
+            }
+        });
+
+        if let Some(ref hec) = hec_template {
+            hec.log_stats();
+        }
+    }
+
+    /// Re-send the batches of a dead-letter directory through the configured HEC.
+    /// Replayed batches are removed; batches that fail again are kept for a later replay.
+    pub fn replay_dead_letters(&self, dir: &Path) {
+        let hec_template = match self.hec_template {
+            Some(ref h) => h,
+            None => {
+                error!("HEC template not configured; call configure() first.");
+                return;
+            }
+        };
+
+        let batches = DeadLetterQueue::list_batches(dir);
+        if batches.is_empty() {
+            info!("No dead-letter batches to replay in {}", dir.display());
+            return;
+        }
+        info!("Replaying {} dead-letter batches from {}", batches.len(), dir.display());
+
+        let mut hec = hec_template.fork();
+
+        let mut replayed = 0usize;
+        for batch_path in &batches {
+            let Some(events) = DeadLetterQueue::read_batch(batch_path) else {
+                continue;
+            };
+
+            // One batch per request, so the result maps to exactly one file
+            if hec.replay_batch(events) {
+                DeadLetterQueue::remove_batch(batch_path);
+                replayed += 1;
+            } else {
+                error!("Replay of {} failed, keeping it", batch_path.display());
+            }
+        }
+
+        info!("Replayed {}/{} dead-letter batches", replayed, batches.len());
+        hec.log_stats();
+    }
+
+    /// Compare the number of events Splunk indexed for each file with the expected_event_count
+    /// of its summary events (quarantined events excluded), write the discrepancy report and
+    /// select the files to re-send (with `resend`) among those that came up short. Files with
+    /// events already indexed are only selected with `resend_partial`, as that duplicates those
+    /// events. Returns true when every file matches, and the files to re-send.
+    pub fn verify(&self, tuples: &[FileTuple], resend: bool, resend_partial: bool, report_path: &Path) -> (bool, Vec<FileTuple>) {
+        let helper = match self.splunk {
+            Some(ref h) => h,
+            None => {
+                error!("Management API not configured; call configure_verify() first.");
+                return (false, Vec::new());
+            }
+        };
+
+        // 1. Count the events and summary events of every sourcefile, in all indexes
+        let sourcefiles: Vec<String> = tuples
+            .iter()
+            .map(|t| t.file_path.to_string_lossy().to_string())
+            .collect();
+        info!("Counting indexed events of {} sourcefiles", sourcefiles.len());
+
+        let mut rows = Vec::new();
+        for chunk in sourcefiles.chunks(FILES_PER_SEARCH) {
+            let query = count_query(chunk);
+            debug!("Search: {}", query);
+            match helper.search_export(&query) {
+                Some(r) => rows.extend(r),
+                None => {
+                    error!("Verification search failed.");
+                    return (false, Vec::new());
+                }
+            }
+        }
+        let counts = parse_counts(&rows);
+
+        // 2. Compare with the files of the input
+        let quarantined = DeadLetterQueue::count_by_source(&self.quarantine_dir());
+        let reports = reconcile(&sourcefiles, &counts, &quarantined);
+
+        let mut per_status: HashMap<FileStatus, usize> = HashMap::new();
+        for report in &reports {
+            *per_status.entry(report.status).or_insert(0) += 1;
+            if report.status != FileStatus::Ok {
+                warn!(
+                    "{:?}: {:?}, {} events indexed, {} expected",
+                    report.sourcefile,
+                    report.status,
+                    report.indexed,
+                    report.expected.map_or("?".to_string(), |e| e.to_string())
+                );
+            }
+        }
+        let discrepancies = reports.len() - per_status.get(&FileStatus::Ok).copied().unwrap_or(0);
+        info!(
+            "Verification: {} files, {} ok, {} short, {} excess, {} without summary, {} missing",
+            reports.len(),
+            per_status.get(&FileStatus::Ok).unwrap_or(&0),
+            per_status.get(&FileStatus::Short).unwrap_or(&0),
+            per_status.get(&FileStatus::Excess).unwrap_or(&0),
+            per_status.get(&FileStatus::NoSummary).unwrap_or(&0),
+            per_status.get(&FileStatus::Missing).unwrap_or(&0),
+        );
+
+        // 3. Discrepancy report
+        match File::create(report_path) {
+            Ok(f) => {
+                if let Err(e) = serde_json::to_writer_pretty(BufWriter::new(f), &reports) {
+                    error!("Failed to write verification report {:?}: {}", report_path, e);
+                } else {
+                    info!("Verification report written to {:?}", report_path);
+                }
+            }
+            Err(e) => error!("Failed to create verification report {:?}: {}", report_path, e),
+        }
+
+        // 4. Files to re-send as a whole: only safe when nothing of them is indexed
+        let mut to_resend = Vec::new();
+        if resend {
+            let mut short: HashSet<&str> = HashSet::new();
+            for report in reports.iter().filter(|r| r.status.needs_resend()) {
+                if report.indexed == 0 {
+                    short.insert(report.sourcefile.as_str());
+                } else if resend_partial {
+                    warn!(
+                        "Re-sending {:?} as a whole: its {} indexed events will be duplicated",
+                        report.sourcefile, report.indexed
+                    );
+                    short.insert(report.sourcefile.as_str());
+                } else {
+                    warn!(
+                        "Not re-sending {:?}: its {} indexed events would be duplicated (see --resend-partial)",
+                        report.sourcefile, report.indexed
+                    );
+                }
+            }
+            to_resend = tuples
+                .iter()
+                .filter(|t| short.contains(t.file_path.to_string_lossy().as_ref()))
+                .cloned()
+                .collect();
+        }
+
+        (discrepancies == 0, to_resend)
+    }
+
+    /// Checkpoint progress of a file or archive member, None if it is already done.
+    /// `on_disk` is the file (or archive) whose size and mtime identify the content.
+    fn track_progress(&self, file_tuples: &FileTuple, on_disk: &Path) -> Option<Option<Arc<FileProgress>>> {
+        // Checkpoint: skip files fully ingested by a previous run (--resume)
+        let progress = self
+            .checkpoint
+            .as_ref()
+            .and_then(|j| j.track(&file_tuples.file_path, on_disk));
+        if let Some(ref p) = progress
+            && p.is_done()
+        {
+            info!(
+                "Skipping {:?}: already ingested according to the checkpoint journal",
+                file_tuples.file_path
             );
+            return None;
         }
+        Some(progress)
     }
 
-    fn process_file(&self, hec_template: Option<&HttpEventCollector>, file_tuples: &FileTuple,normalize_dir: Option<&PathBuf>,) {
+    fn process_file(&self, collectors: Option<&WorkerCollectors>, file_tuples: &FileTuple, normalize_dir: Option<&PathBuf>) {
         let file = &file_tuples.file_path;
 
-        let mut ext = file
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("")
-            .to_ascii_lowercase();
+        let progress = match self.track_progress(file_tuples, file) {
+            Some(p) => p,
+            None => return,
+        };
+
+        // Open the input file for reading (decompressed on the fly if needed)
+        let input = match open_decompressed(file) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Failed to open file {:?}: {}", file, e);
+                return;
+            }
+        };
+
+        self.process_input(collectors, file_tuples, normalize_dir, progress, input);
+    }
+
+    /// Ingest the matched members of an archive.
+    ///
+    /// A reader thread walks the archive once and streams each wanted member through a
+    /// channel; members are processed one after the other, their lines on the pool.
+    fn process_archive(
+        &self,
+        collectors: Option<&WorkerCollectors>,
+        archive: &Path,
+        kind: ArchiveKind,
+        members: &[&FileTuple],
+        normalize_dir: Option<&PathBuf>,
+    ) {
+        // 1. Members still to ingest, by name inside the archive
+        let mut pending: HashMap<&str, (&FileTuple, Option<Arc<FileProgress>>)> = HashMap::new();
+        for file_tuples in members {
+            let name = match file_tuples.archive {
+                Some(ref m) => m.name.as_str(),
+                None => continue,
+            };
+            if let Some(progress) = self.track_progress(file_tuples, archive) {
+                pending.insert(name, (file_tuples, progress));
+            }
+        }
+
+        if pending.is_empty() {
+            return;
+        }
+
+        info!("Reading {} members from archive {:?}", pending.len(), archive);
+
+        let wanted: HashSet<&str> = pending.keys().copied().collect();
+        let (member_tx, member_rx) = chan::bounded::<(String, chan::Receiver<io::Result<Vec<u8>>>)>(0);
+
+        std::thread::scope(|scope| {
+            // 2. Reader thread: sequential pass over the archive
+            let wanted = &wanted;
+            scope.spawn(move || {
+                let result = stream_members(archive, kind, wanted, |name, data| {
+                    let (tx, rx) = chan::bounded(16);
+                    if member_tx.send((name.to_string(), rx)).is_err() {
+                        return Err(io::Error::other("archive ingestion stopped"));
+                    }
+                    send_member(data, &tx);
+                    Ok(())
+                });
+
+                if let Err(e) = result {
+                    error!("Error reading archive {:?}: {}", archive, e);
+                }
+            });
+
+            // 3. Process members as they are streamed
+            for (name, rx) in member_rx {
+                // Unknown or duplicated member: dropping rx makes the reader skip it
+                let (file_tuples, progress) = match pending.remove(name.as_str()) {
+                    Some(p) => p,
+                    None => continue,
+                };
+
+                match decompress(ChannelReader::new(rx), &file_tuples.file_path) {
+                    Ok(input) => self.process_input(collectors, file_tuples, normalize_dir, progress, input),
+                    Err(e) => error!("Failed to read {:?}: {}", file_tuples.file_path, e),
+                }
+            }
+        });
+    }
+
+    fn process_input(
+        &self,
+        collectors: Option<&WorkerCollectors>,
+        file_tuples: &FileTuple,
+        normalize_dir: Option<&PathBuf>,
+        progress: Option<Arc<FileProgress>>,
+        input: Box<dyn Read + Send>,
+    ) {
+        // Extension of the decompressed content ("a.jsonl.gz" → "jsonl")
+        let mut ext = inner_extension(&file_tuples.file_path);
 
         if !self.input_type.is_none(){
             ext = self.input_type.clone().unwrap();
         }
-        // Dispatch processing based on file type: csv, jsonl or raw (eg. access logs)
+
+        // Dispatch processing based on file type: csv, jsonl, evtx or raw (eg. access logs)
         match ext.as_str() {
-            "json" | "jsonl" => self.run_parallel_line_pipeline("JSON/JSONL", "output.jsonl", hec_template, file_tuples, normalize_dir, ParseMode::Json,),
-            "csv" => self.run_parallel_csv_pipeline(hec_template, file_tuples, normalize_dir),
-            _ => self.run_parallel_line_pipeline("RAW", "output.raw", hec_template, file_tuples, normalize_dir,ParseMode::Raw,)
+            "json" | "jsonl" => self.run_parallel_line_pipeline("JSON/JSONL", "output.jsonl", collectors, file_tuples, normalize_dir, ParseMode::Json, progress, input),
+            "csv" => self.run_parallel_csv_pipeline(collectors, file_tuples, normalize_dir, progress, input),
+            "evtx" => self.run_evtx_pipeline(collectors, file_tuples, normalize_dir, progress, input),
+            _ => self.run_parallel_line_pipeline("RAW", "output.raw", collectors, file_tuples, normalize_dir, ParseMode::Raw, progress, input)
         }
     }
 
@@ -1,15 +1,21 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 
 use clap::Parser;
 use env_logger;
-use log::info;
+use indexmap::IndexMap;
+use log::{error, info};
 
 mod utils;
 mod splunk_utils;
 mod json2splunk;
 
+use crate::splunk_utils::dead_letter::DeadLetterQueue;
+use crate::utils::checkpoint::CheckpointJournal;
 use crate::utils::file_matcher::FileMatcher;
+use crate::utils::lookup::{self, LookupDefinition};
+use crate::utils::vrl_test::run_vrl_tests;
 use json2splunk::Json2Splunk;
 use num_cpus;
 
@@ -21,19 +27,19 @@ struct Cli {
     #[arg(short = 'v', long = "verbosity", default_value = "INFO")]
     verbosity: String,
 
-    /// Directory to index (required)
-    #[arg(long = "input", required = true)]
-    input: PathBuf,
+    /// Directory to index (required unless --replay-dlq or --test-vrl is provided)
+    #[arg(long = "input", required_unless_present_any = ["replay_dlq", "test_vrl"])]
+    input: Option<PathBuf>,
 
     /// Optional : Specifies the file type input. Defaults is None.
     #[arg(long = "input_type")]
     input_type: Option<String>,
 
     /// Splunk index name.
-    /// Required unless --normalize_test_dir is provided.
+    /// Required unless --normalize_test_dir, --replay-dlq or --test-vrl is provided.
     #[arg(
         long = "index",
-        required_unless_present = "normalize_test_dir"
+        required_unless_present_any = ["normalize_test_dir", "replay_dlq", "test_vrl"]
     )]
     index: Option<String>,
 
@@ -65,6 +71,60 @@ struct Cli {
     /// instead of sending events to Splunk (VRL normalize test mode).
     #[arg(long, value_name = "DIR")]
     normalize_test_dir: Option<PathBuf>,
+
+    /// Directory where batches rejected by HEC (or failing after retries) are written.
+    #[arg(long, value_name = "DIR", default_value = "json2splunk_dlq")]
+    dlq_dir: PathBuf,
+
+    /// Re-send the dead-letter batches of DIR to Splunk instead of ingesting --input.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["input", "normalize_test_dir"])]
+    replay_dlq: Option<PathBuf>,
+
+    /// Checkpoint journal recording completed files and the last delivered line of partial ones.
+    #[arg(long, value_name = "FILE", default_value = "json2splunk_checkpoint.jsonl")]
+    checkpoint: PathBuf,
+
+    /// Resume an interrupted ingestion: skip files completed in the checkpoint journal
+    /// and data already delivered for partially processed ones.
+    #[arg(long)]
+    resume: bool,
+
+    /// Verify mode: compare the events indexed in Splunk for each file of --input with the
+    /// expected_event_count of its summary events, instead of ingesting.
+    #[arg(long, conflicts_with_all = ["normalize_test_dir", "replay_dlq"])]
+    verify: bool,
+
+    /// With --verify, re-send the files with fewer events in Splunk than expected, when none
+    /// of their events is indexed yet.
+    #[arg(long, requires = "verify")]
+    resend_short: bool,
+
+    /// With --resend-short, also re-send the files partly indexed, as a whole: their indexed
+    /// events are duplicated.
+    #[arg(long, requires = "resend_short")]
+    resend_partial: bool,
+
+    /// With --verify, per-file discrepancy report (JSON).
+    #[arg(long, value_name = "FILE", default_value = "json2splunk_verify_report.json")]
+    verify_report: PathBuf,
+
+    /// Maximum number of events per second sent to HEC, all worker threads included.
+    #[arg(long = "max-eps", value_name = "EVENTS")]
+    max_eps: Option<f64>,
+
+    /// Maximum throughput to HEC in megabytes per second (uncompressed event data), all worker threads included.
+    #[arg(long = "max-mbps", value_name = "MB")]
+    max_mbps: Option<f64>,
+
+    /// Lookup table for the VRL lookup() function, as NAME=PATH[:MODE] (CSV or JSONL, MODE: exact,
+    /// case_insensitive or cidr). Repeatable; overrides a table of the same name in the patterns file.
+    #[arg(long = "lookup", value_name = "NAME=PATH")]
+    lookup: Vec<String>,
+
+    /// Run the test cases of the VRL scripts of DIR (<script>.test.yml next to <script>.vrl)
+    /// instead of ingesting. Exits with code 1 when a case fails.
+    #[arg(long = "test-vrl", value_name = "DIR", conflicts_with_all = ["input", "replay_dlq", "verify"])]
+    test_vrl: Option<PathBuf>,
 }
 
 fn main() {
@@ -72,21 +132,64 @@ fn main() {
 
     env_logger::Builder::from_env(
         env_logger::Env::default().default_filter_or(cli.verbosity.as_str())
-    ).init();
+    )
+    // The EVTX parser logs every chunk at INFO level
+    .filter_module("evtx", log::LevelFilter::Warn)
+    .init();
 
     let start = Instant::now();
     info!("Using {} CPUs", cli.nb_cpu);
 
-    let mut fm = FileMatcher::new(cli.indexer_patterns.clone(), cli.test, cli.ext.clone());
-    fm.create_dataframe(&cli.input);
+    let mut j2s = Json2Splunk::new(cli.normalize_test_dir.clone());
+    j2s.set_vrl_dir(cli.vrl_dir.clone());
+    j2s.dlq_dir = cli.dlq_dir.clone();
+    j2s.max_eps = cli.max_eps;
+    j2s.max_mbps = cli.max_mbps;
+
+    // VRL test mode: only the --lookup tables, no patterns file nor Splunk
+    if let Some(ref dir) = cli.test_vrl {
+        let ok = load_lookups(IndexMap::new(), &cli.lookup) && run_vrl_tests(dir);
+        info!("Finished in {:?}", start.elapsed());
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // Dead-letter replay mode: no input scan, only re-send failed batches
+    if let Some(ref dlq) = cli.replay_dlq {
+        // Events carry their index: every index of the batches must be allowed for the token
+        let batch_indexes = DeadLetterQueue::indexes(dlq);
+        let index_str = match cli.index.clone().or_else(|| batch_indexes.first().cloned()) {
+            Some(index) => index,
+            None => {
+                eprintln!("Error: no index in the dead-letter metadata of {:?}; pass --index.", dlq);
+                std::process::exit(1);
+            }
+        };
+        j2s.routed_indexes = batch_indexes.into_iter().filter(|i| *i != index_str).collect();
+        if j2s.configure(&index_str, cli.nb_cpu, cli.test, &cli.config_spl) && !cli.test {
+            j2s.replay_dead_letters(dlq);
+        }
+        info!("Finished in {:?}", start.elapsed());
+        return;
+    }
+
+    let input = cli.input.clone().expect("--input is required");
+    let mut fm = FileMatcher::new(
+        cli.indexer_patterns.clone(),
+        cli.test,
+        cli.ext.clone(),
+        cli.index.as_deref().unwrap_or(""),
+    );
+    fm.create_dataframe(&input);
     fm.print_statistics();
 
+    // Lookup tables are loaded once, before any VRL script is compiled
+    if !load_lookups(fm.lookups.clone(), &cli.lookup) {
+        std::process::exit(1);
+    }
+
     let id_time = Instant::now();
     info!("Input identification completed in {:?}", id_time.duration_since(start));
 
-    let mut j2s = Json2Splunk::new(cli.normalize_test_dir.clone());
-    j2s.set_vrl_dir(cli.vrl_dir.clone());
-
     let normalize_mode = cli.normalize_test_dir.is_some();
     let index_str = cli.index.as_deref().unwrap_or("");
 
@@ -95,13 +198,63 @@ fn main() {
         std::process::exit(1);
     }
 
+    // The checkpoint journal only makes sense when data is actually sent to Splunk
+    // Verify mode re-sends whole files: no checkpoint journal
+    if !normalize_mode && !cli.test && !cli.verify {
+        match CheckpointJournal::open(&cli.checkpoint, cli.resume) {
+            Ok(journal) => {
+                info!("Checkpoint journal: {}", journal.path().display());
+                j2s.checkpoint = Some(Arc::new(journal));
+            }
+            Err(e) => {
+                eprintln!("Error: cannot open checkpoint journal {:?}: {}", cli.checkpoint, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Indexes of the patterns' index templates are created along with --index
+    j2s.routed_indexes = fm.routed_indexes();
+
     if !cli.input_type.is_none() {
         j2s.input_type = cli.input_type.clone();
     }
 
+    if cli.verify {
+        // Searches only: the index and HEC input are not touched
+        let (ok, to_resend) = if j2s.configure_verify(&cli.config_spl) {
+            j2s.verify(&fm.list_of_tuples, cli.resend_short, cli.resend_partial, &cli.verify_report)
+        } else {
+            (false, Vec::new())
+        };
+        // Re-sending writes to Splunk: the full setup only happens then
+        if !to_resend.is_empty() && j2s.configure(index_str, cli.nb_cpu, cli.test, &cli.config_spl) {
+            info!("Re-sending {} files that came up short", to_resend.len());
+            j2s.ingest(&to_resend);
+        }
+        info!("Finished in {:?}", start.elapsed());
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
     if j2s.configure(index_str, cli.nb_cpu, cli.test, &cli.config_spl) {
         j2s.ingest(&fm.list_of_tuples);
     }
 
     info!("Finished in {:?}", start.elapsed());
 }
+
+/// Load the lookup tables of the patterns file and of --lookup (which wins on a name clash).
+fn load_lookups(mut lookups: IndexMap<String, LookupDefinition>, cli_lookups: &[String]) -> bool {
+    for arg in cli_lookups {
+        match LookupDefinition::from_cli(arg) {
+            Ok((name, def)) => {
+                lookups.insert(name, def);
+            }
+            Err(e) => {
+                error!("{}", e);
+                return false;
+            }
+        }
+    }
+    lookup::init(&lookups)
+}
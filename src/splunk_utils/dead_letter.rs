@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use log::{error, info, warn};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::splunk_utils::http_event_collector::EventOrigin;
+
+/// On-disk dead-letter queue for batches HEC rejected or that failed after retries.
+///
+/// Each failed batch is stored as two files:
+///   - <stamp>-<uuid>.ndjson     : HEC-ready events, one per line (can be POSTed as-is)
+///   - <stamp>-<uuid>.meta.json  : source files, line ranges, HTTP status and Splunk error body
+pub struct DeadLetterQueue {
+    dir: PathBuf,
+}
+
+impl DeadLetterQueue {
+    pub fn new(dir: PathBuf) -> Self {
+        DeadLetterQueue { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Persist a failed batch. Returns false if the batch could not be written,
+    /// in which case it is really lost.
+    pub fn write(
+        &self,
+        events: &[String],
+        origins: &[EventOrigin],
+        index: Option<&str>,
+        status: Option<u16>,
+        error_body: &str,
+    ) -> bool {
+        if let Err(e) = fs::create_dir_all(&self.dir) {
+            error!("Failed to create dead-letter directory {}: {}", self.dir.display(), e);
+            return false;
+        }
+
+        let stem = format!(
+            "{}-{}",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+            Uuid::new_v4()
+        );
+        let data_path = self.dir.join(format!("{stem}.ndjson"));
+        let meta_path = self.dir.join(format!("{stem}.meta.json"));
+
+        // 1. Events, with the index embedded so the file does not depend on the URI it was posted to
+        let written = File::create(&data_path).and_then(|f| {
+            let mut w = BufWriter::new(f);
+            for event in events {
+                let line = match (index, serde_json::from_str::<Value>(event)) {
+                    (Some(idx), Ok(Value::Object(mut map))) if !map.contains_key("index") => {
+                        map.insert("index".to_string(), Value::String(idx.to_string()));
+                        Value::Object(map).to_string()
+                    }
+                    _ => event.trim_end().to_string(),
+                };
+                w.write_all(line.as_bytes())?;
+                w.write_all(b"\n")?;
+            }
+            w.flush()
+        });
+        if let Err(e) = written {
+            error!("Failed to write dead-letter batch {}: {}", data_path.display(), e);
+            return false;
+        }
+
+        // 2. Metadata: line range per source file (line 0 = event without input line)
+        let mut ranges: BTreeMap<&str, Option<(usize, usize)>> = BTreeMap::new();
+        for origin in origins {
+            let range = ranges.entry(&origin.sourcefile).or_insert(None);
+            if origin.line == 0 {
+                continue;
+            }
+            *range = Some(match *range {
+                Some((first, last)) => (first.min(origin.line), last.max(origin.line)),
+                None => (origin.line, origin.line),
+            });
+        }
+        let sources: Vec<Value> = ranges
+            .into_iter()
+            .map(|(file, range)| {
+                json!({
+                    "sourcefile": file,
+                    "first_line": range.map(|r| r.0),
+                    "last_line": range.map(|r| r.1),
+                })
+            })
+            .collect();
+
+        let meta = json!({
+            "created": chrono::Utc::now().to_rfc3339(),
+            "events": events.len(),
+            "index": index,
+            "http_status": status,
+            "error": error_body,
+            "sources": sources,
+        });
+
+        if let Err(e) = fs::write(&meta_path, serde_json::to_string_pretty(&meta).unwrap_or_default()) {
+            warn!("Failed to write dead-letter metadata {}: {}", meta_path.display(), e);
+        }
+
+        error!(
+            "Batch of {} events written to dead-letter queue {}",
+            events.len(),
+            data_path.display()
+        );
+        true
+    }
+
+    /// List dead-lettered batches in a directory, oldest first.
+    pub fn list_batches(dir: &Path) -> Vec<PathBuf> {
+        let mut out: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(rd) => rd
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("ndjson"))
+                .collect(),
+            Err(e) => {
+                error!("Cannot read dead-letter directory {}: {}", dir.display(), e);
+                Vec::new()
+            }
+        };
+        out.sort();
+        out
+    }
+
+    /// Target indexes of the batches of `dir`, from their metadata, sorted.
+    pub fn indexes(dir: &Path) -> Vec<String> {
+        let indexes: BTreeSet<String> = Self::list_batches(dir)
+            .iter()
+            .filter_map(|batch| fs::read_to_string(batch.with_extension("meta.json")).ok())
+            .filter_map(|s| serde_json::from_str::<Value>(&s).ok())
+            .filter_map(|meta| meta.get("index")?.as_str().map(|i| i.to_string()))
+            .collect();
+        indexes.into_iter().collect()
+    }
+
+    /// Number of events per source file in the batches of `dir`, counted by distinct line
+    /// (an event quarantined again by a re-send counts once). Meant for the quarantine,
+    /// where each batch holds a single event.
+    pub fn count_by_source(dir: &Path) -> HashMap<String, u64> {
+        if !dir.is_dir() {
+            return HashMap::new();
+        }
+
+        let mut lines: HashMap<String, HashSet<u64>> = HashMap::new();
+        for batch in Self::list_batches(dir) {
+            let meta_path = batch.with_extension("meta.json");
+            let meta = match fs::read_to_string(&meta_path)
+                .ok()
+                .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+            {
+                Some(m) => m,
+                None => {
+                    warn!("Cannot read dead-letter metadata {}", meta_path.display());
+                    continue;
+                }
+            };
+            for source in meta.get("sources").and_then(|s| s.as_array()).into_iter().flatten() {
+                let file = source.get("sourcefile").and_then(|f| f.as_str());
+                let first = source.get("first_line").and_then(|l| l.as_u64());
+                let last = source.get("last_line").and_then(|l| l.as_u64());
+                if let (Some(file), Some(first), Some(last)) = (file, first, last) {
+                    lines.entry(file.to_string()).or_default().extend(first..=last);
+                }
+            }
+        }
+
+        lines.into_iter().map(|(file, l)| (file, l.len() as u64)).collect()
+    }
+
+    /// Read back the HEC events of a dead-lettered batch.
+    pub fn read_batch(path: &Path) -> Option<Vec<String>> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => {
+                error!("Cannot open dead-letter batch {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        let mut events = Vec::new();
+        for line in BufReader::new(file).lines() {
+            match line {
+                Ok(l) if l.trim().is_empty() => {}
+                Ok(l) => events.push(l),
+                Err(e) => {
+                    error!("Error reading dead-letter batch {}: {}", path.display(), e);
+                    return None;
+                }
+            }
+        }
+        Some(events)
+    }
+
+    /// Remove a batch (and its metadata) once it has been replayed.
+    pub fn remove_batch(path: &Path) {
+        let meta_path = path.with_extension("meta.json");
+        for p in [path, meta_path.as_path()] {
+            if let Err(e) = fs::remove_file(p) {
+                warn!("Failed to remove replayed dead-letter file {}: {}", p.display(), e);
+            }
+        }
+        info!("Replayed and removed dead-letter batch {}", path.display());
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use reqwest::blocking::Client;
+
+/// Delay before an unhealthy endpoint is checked again with /services/collector/health.
+const HEALTH_RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Timeout of a health check, shorter than the client timeout so a dead receiver
+/// does not stall a worker for long.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Consecutive 503 "Server is busy" (or 429 / 5xx) after which an endpoint is marked unhealthy.
+const MAX_CONSECUTIVE_BUSY: u32 = 3;
+
+/// How batches are spread across HEC endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Balance {
+    RoundRobin,
+    /// Endpoint with the fewest requests in flight
+    LeastLoaded,
+}
+
+impl Balance {
+    pub fn from_name(name: &str) -> Option<Balance> {
+        match name.to_ascii_lowercase().replace('-', "_").as_str() {
+            "round_robin" => Some(Balance::RoundRobin),
+            "least_loaded" => Some(Balance::LeastLoaded),
+            _ => None,
+        }
+    }
+}
+
+struct EndpointState {
+    healthy: bool,
+    consecutive_busy: u32,
+    /// When an unhealthy endpoint may be checked again
+    next_check: Instant,
+}
+
+/// One HEC receiver.
+pub struct HecEndpoint {
+    /// "https://host:8088/services/collector"
+    base_uri: String,
+    ssl: bool,
+    in_flight: AtomicUsize,
+    state: Mutex<EndpointState>,
+}
+
+impl HecEndpoint {
+    pub fn new(host: &str, port: u16, ssl: bool) -> Self {
+        let protocol = if ssl { "https" } else { "http" };
+        HecEndpoint {
+            base_uri: format!("{}://{}:{}/services/collector", protocol, host, port),
+            ssl,
+            in_flight: AtomicUsize::new(0),
+            state: Mutex::new(EndpointState {
+                healthy: true,
+                consecutive_busy: 0,
+                next_check: Instant::now(),
+            }),
+        }
+    }
+}
+
+/// HEC endpoints shared by every collector (all worker threads).
+///
+/// Endpoints that refuse connections or keep answering 503 are marked unhealthy and
+/// skipped; they are checked again with /services/collector/health every
+/// HEALTH_RECHECK_INTERVAL. When no endpoint is healthy, all of them are used.
+pub struct EndpointPool {
+    endpoints: Vec<HecEndpoint>,
+    balance: Balance,
+    next: AtomicUsize,
+    client: Client,
+}
+
+/// An endpoint picked for one request. Counts as in flight until dropped.
+pub struct InFlight<'a> {
+    pool: &'a EndpointPool,
+    pub index: usize,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.pool.endpoints[self.index].in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl EndpointPool {
+    pub fn new(endpoints: Vec<HecEndpoint>, balance: Balance, client: Client) -> Self {
+        assert!(!endpoints.is_empty(), "at least one HEC endpoint is required");
+        EndpointPool {
+            endpoints,
+            balance,
+            next: AtomicUsize::new(0),
+            client,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub fn base_uri(&self, index: usize) -> &str {
+        &self.endpoints[index].base_uri
+    }
+
+    pub fn base_uris(&self) -> Vec<&str> {
+        self.endpoints.iter().map(|e| e.base_uri.as_str()).collect()
+    }
+
+    pub fn uses_tls(&self) -> bool {
+        self.endpoints.iter().any(|e| e.ssl)
+    }
+
+    fn is_healthy(&self, index: usize) -> bool {
+        self.endpoints[index].state.lock().unwrap().healthy
+    }
+
+    /// GET /services/collector/health on one endpoint and update its state.
+    fn check_health(&self, index: usize) -> bool {
+        let uri = format!("{}/health", self.endpoints[index].base_uri);
+
+        let healthy = match self.client.get(&uri).timeout(HEALTH_CHECK_TIMEOUT).send() {
+            Ok(resp) if resp.status().is_success() => {
+                info!("HEC is healthy: {}", uri);
+                true
+            }
+            Ok(resp) => {
+                let status = resp.status();
+                // 503 {"text":"HEC is unhealthy, queues are full","code":18}
+                error!("HEC health check failed on {}: status={} body={}", uri, status, resp.text().unwrap_or_default());
+                false
+            }
+            Err(e) => {
+                error!("Cannot reach HEC at {}: {}", uri, e);
+                false
+            }
+        };
+
+        let mut st = self.endpoints[index].state.lock().unwrap();
+        st.healthy = healthy;
+        st.consecutive_busy = 0;
+        st.next_check = Instant::now() + HEALTH_RECHECK_INTERVAL;
+        healthy
+    }
+
+    /// Check every endpoint. Returns the number of healthy ones.
+    pub fn check_all(&self) -> usize {
+        (0..self.endpoints.len()).filter(|&i| self.check_health(i)).count()
+    }
+
+    /// Check again the unhealthy endpoints whose recheck delay expired.
+    fn recheck_due(&self) {
+        let now = Instant::now();
+        for (i, endpoint) in self.endpoints.iter().enumerate() {
+            {
+                let mut st = endpoint.state.lock().unwrap();
+                if st.healthy || st.next_check > now {
+                    continue;
+                }
+                // Claim the check so other threads do not run it concurrently
+                st.next_check = now + HEALTH_RECHECK_INTERVAL;
+            }
+            if self.check_health(i) {
+                info!("HEC endpoint {} is back in the pool", endpoint.base_uri);
+            }
+        }
+    }
+
+    /// Pick the endpoint of the next request.
+    pub fn acquire(&self) -> InFlight<'_> {
+        self.recheck_due();
+
+        let mut candidates: Vec<usize> = (0..self.endpoints.len()).filter(|&i| self.is_healthy(i)).collect();
+        if candidates.is_empty() {
+            // Nothing healthy: keep trying all endpoints rather than failing every batch
+            candidates = (0..self.endpoints.len()).collect();
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        let index = match self.balance {
+            Balance::RoundRobin => candidates[start],
+            // Ties are broken by the round-robin position so equal endpoints share the load
+            Balance::LeastLoaded => (0..candidates.len())
+                .map(|k| candidates[(start + k) % candidates.len()])
+                .min_by_key(|&i| self.endpoints[i].in_flight.load(Ordering::Relaxed))
+                .unwrap(),
+        };
+
+        self.endpoints[index].in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight { pool: self, index }
+    }
+
+    /// True if another endpoint than `index` is currently healthy.
+    pub fn has_alternative(&self, index: usize) -> bool {
+        (0..self.endpoints.len()).any(|i| i != index && self.is_healthy(i))
+    }
+
+    pub fn report_success(&self, index: usize) {
+        self.endpoints[index].state.lock().unwrap().consecutive_busy = 0;
+    }
+
+    /// 503 "Server is busy", 429 or a 5xx: the endpoint is taken out after MAX_CONSECUTIVE_BUSY in a row.
+    pub fn report_busy(&self, index: usize, status: u16) {
+        let endpoint = &self.endpoints[index];
+        let mut st = endpoint.state.lock().unwrap();
+        st.consecutive_busy += 1;
+        if st.healthy && st.consecutive_busy >= MAX_CONSECUTIVE_BUSY {
+            warn!(
+                "HEC endpoint {} failed {} requests in a row (last status {}); marked unhealthy",
+                endpoint.base_uri, st.consecutive_busy, status
+            );
+            st.healthy = false;
+            st.next_check = Instant::now() + HEALTH_RECHECK_INTERVAL;
+        }
+    }
+
+    /// Connection error or timeout: the endpoint is taken out at once.
+    pub fn report_unreachable(&self, index: usize, err: &reqwest::Error) {
+        let endpoint = &self.endpoints[index];
+        let mut st = endpoint.state.lock().unwrap();
+        if st.healthy {
+            warn!("HEC endpoint {} is unreachable ({}); marked unhealthy", endpoint.base_uri, err);
+            st.healthy = false;
+            st.next_check = Instant::now() + HEALTH_RECHECK_INTERVAL;
+        }
+    }
+}
@@ -1,36 +1,150 @@
-use std::time::Duration;
-
-use log::{debug, error};
+use std::collections::{BTreeSet, HashSet};
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use flate2::Compression as GzCompression;
+use flate2::write::GzEncoder;
+use log::{debug, error, info, warn};
 use reqwest::blocking::Client;
 use reqwest::header::{HeaderMap, HeaderValue};
-use serde_json::Value;
+use serde_json::{json, Value};
 use uuid::Uuid;
 
+use crate::splunk_utils::dead_letter::DeadLetterQueue;
+use crate::splunk_utils::hec_endpoints::EndpointPool;
+use crate::splunk_utils::rate_limit::RateLimiter;
+use crate::splunk_utils::retry::{describe_error, is_retryable_status, retry_after, Retries, RetryPolicy};
+use crate::utils::checkpoint::FileProgress;
+
+/// Delay between two polls of /services/collector/ack while waiting for pending acks.
+const ACK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of in-flight acknowledged batches before the collector polls for acks.
+const MAX_PENDING_ACKS: usize = 32;
+/// Number of times a batch that is never acked is re-sent before giving up.
+const MAX_ACK_RESENDS: u32 = 3;
+/// With gzip, a batch is flushed once its estimated compressed size reaches `max_byte_length`;
+/// its uncompressed size is still bounded to this multiple of `max_byte_length`.
+const MAX_COMPRESSION_FACTOR: usize = 10;
+/// HEC error codes of a batch refused because of one of its events:
+/// 6 "Invalid data format", 15 "Error in handling indexed fields".
+const INVALID_EVENT_CODES: [i64; 2] = [6, 15];
+
+/// Delivery counters shared by every clone of a collector.
+/// In ack mode, an event is only counted as delivered once Splunk acked its batch.
+#[derive(Debug, Default)]
+pub struct HecStats {
+    pub events_sent: AtomicU64,
+    pub events_delivered: AtomicU64,
+    pub batches_failed: AtomicU64,
+    /// Failed batches that ran out of retries (HEC unreachable or overloaded)
+    pub batches_exhausted: AtomicU64,
+    pub batches_dead_lettered: AtomicU64,
+    /// Events refused by HEC, pulled out of their batch and quarantined
+    pub events_quarantined: AtomicU64,
+    /// Size of the batches before compression
+    pub bytes_raw: AtomicU64,
+    /// Size of the request bodies (compressed with gzip)
+    pub bytes_sent: AtomicU64,
+}
+
+/// Where a batched event comes from: source file and line (or CSV row).
+/// Line 0 is used for events that do not map to an input line (summary events, replays).
+#[derive(Clone)]
+pub struct EventOrigin {
+    pub sourcefile: Arc<str>,
+    pub line: usize,
+    /// Checkpoint of the source file, notified once the event is delivered
+    pub progress: Option<Arc<FileProgress>>,
+}
+
+impl EventOrigin {
+    pub fn new(sourcefile: Arc<str>, line: usize, progress: Option<Arc<FileProgress>>) -> Self {
+        EventOrigin { sourcefile, line, progress }
+    }
+}
+
+/// Report delivered (or safely dead-lettered) events to their file checkpoints.
+fn mark_handled(origins: &[EventOrigin]) {
+    for origin in origins {
+        if let Some(ref progress) = origin.progress {
+            progress.complete(origin.line);
+        }
+    }
+}
+
+/// A batch accepted by HEC in ack mode, waiting for its ackId to be confirmed.
+#[derive(Clone)]
+struct PendingAck {
+    ack_id: u64,
+    /// Endpoint that accepted the batch: ackIds are only known to that receiver
+    endpoint: usize,
+    events: Vec<String>,
+    origins: Vec<EventOrigin>,
+    sent_at: Instant,
+    resends: u32,
+}
+
+/// Result of a post to HEC once retries are exhausted.
+enum PostOutcome {
+    /// 2xx on `endpoint`, with the ackId returned by Splunk when indexer acknowledgement is enabled
+    Accepted { ack_id: Option<u64>, endpoint: usize },
+    /// Refused by HEC (4xx other than 429): the batch is not retried
+    Rejected { status: u16, body: String },
+    /// Network errors or transient statuses until the retry policy gave up
+    Exhausted { status: Option<u16>, reason: String },
+}
+
 /// HTTP Event Collector client for sending events to Splunk.
 #[derive(Clone)]
 pub struct HttpEventCollector {
     pub token: String,
-    pub http_event_server: String,
+    /// HEC receivers, shared by all the forks of the collector
+    pub endpoints: Arc<EndpointPool>,
     pub input_type: String,
     pub host: String,
-    pub http_event_port: String,
-    pub http_event_server_ssl: bool,
+    /// Informational: verification is configured on `client`
     pub ssl_verify: bool,
 
     // The client is actually changed by Json2Splunk
-    pub client: Client, 
+    pub client: Client,
 
     pub index: Option<String>,
     pub sourcetype: Option<String>,
     pub pop_null_fields: bool,
 
+    /// Retries of network errors and transient HTTP statuses
+    pub retry: RetryPolicy,
+
+    /// Indexer acknowledgement (useACK) mode
+    pub use_ack: bool,
+    /// Time after which a batch that is still not acked is re-sent
+    pub ack_timeout: Duration,
+    /// X-Splunk-Request-Channel, stable for the lifetime of the collector (see `fork`)
+    channel: String,
+    pending_acks: Vec<PendingAck>,
+    pub stats: Arc<HecStats>,
+    /// Where failed batches are persisted (None = failed batches are only logged)
+    pub dead_letters: Option<Arc<DeadLetterQueue>>,
+    /// Where single events refused by HEC (code 6/15) are persisted, apart from failed batches
+    pub quarantine: Option<Arc<DeadLetterQueue>>,
+    /// Throughput cap shared by all the forks of the collector (--max-eps / --max-mbps)
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+
     batch_events: Vec<String>,
+    batch_origins: Vec<EventOrigin>,
     current_byte_length: usize,
+    /// Size limit of a request body (compressed size with gzip)
     pub max_byte_length: usize,
+    /// Gzip level (0-9) of the request bodies, None to send them uncompressed
+    pub gzip_level: Option<u32>,
+    /// Compressed / raw size of the previous batches, used to fill batches up to `max_byte_length`
+    compression_ratio: Option<f64>,
 }
 impl HttpEventCollector {
-    pub fn new(token: &str, http_event_server: &str, input_type: &str, client: Client) -> Self {
-        
+    pub fn new(token: &str, endpoints: EndpointPool, input_type: &str, client: Client) -> Self {
+
         let host = hostname::get()
             .ok()
             .and_then(|h| h.into_string().ok())
@@ -38,28 +152,49 @@ impl HttpEventCollector {
 
         HttpEventCollector {
             token: token.to_string(),
-            http_event_server: http_event_server.to_string(),
+            endpoints: Arc::new(endpoints),
             input_type: input_type.to_string(),
             host,
-            http_event_port: "8088".to_string(),
-            http_event_server_ssl: true,
             ssl_verify: false,
             client,
             index: None,
             sourcetype: None,
             pop_null_fields: false,
+            retry: RetryPolicy::default(),
+            use_ack: false,
+            ack_timeout: Duration::from_secs(300),
+            channel: Uuid::new_v4().to_string(),
+            pending_acks: Vec::new(),
+            stats: Arc::new(HecStats::default()),
+            dead_letters: None,
+            quarantine: None,
+            rate_limiter: None,
             batch_events: Vec::new(),
+            batch_origins: Vec::new(),
             current_byte_length: 0,
             max_byte_length: 100_000,
+            gzip_level: None,
+            compression_ratio: None,
         }
     }
 
-    /// Build server URI like the Python version.
-    pub fn server_uri(&self) -> String {
-        let protocol = if self.http_event_server_ssl { "https" } else { "http" };
+    /// Clone the collector for a worker thread: same settings and shared stats,
+    /// but its own request channel and empty buffers.
+    pub fn fork(&self) -> Self {
+        let mut hec = self.clone();
+        hec.channel = Uuid::new_v4().to_string();
+        hec.pending_acks = Vec::new();
+        hec.batch_events.clear();
+        hec.batch_origins.clear();
+        hec.current_byte_length = 0;
+        hec
+    }
 
+    /// Path and query of the input, appended to the base URI of an endpoint.
+    /// Built like the Python version.
+    pub fn input_path(&self) -> String {
         let mut input_url = if self.input_type == "raw" {
-            format!("/raw?channel={}", Uuid::new_v4())
+            format!("/raw?channel={}", self.channel)
         } else {
             "/event".to_string()
         };
@@ -84,117 +219,498 @@ impl HttpEventCollector {
             input_url.push_str(&format!("index={}", idx));
         }
 
-        format!(
-            "{}://{}:{}/services/collector{}",
-            protocol, self.http_event_server, self.http_event_port, input_url
-        )
+        input_url
+    }
+
+    /// Check that HEC is up and accepting data (GET /services/collector/health on every endpoint).
+    /// Unhealthy endpoints are left out until they recover.
+    pub fn check_health(&self) -> bool {
+        let healthy = self.endpoints.check_all();
+        if healthy > 0 && healthy < self.endpoints.len() {
+            warn!("{}/{} HEC endpoints are healthy", healthy, self.endpoints.len());
+        }
+        healthy > 0
+    }
+
+    /// Request body of a batch: the concatenated events, gzipped if enabled.
+    /// Updates the compression ratio estimate used to size the next batches.
+    fn encode_body(&mut self, events: &[String]) -> Vec<u8> {
+        let payload = events.join("");
+        self.stats.bytes_raw.fetch_add(payload.len() as u64, Ordering::Relaxed);
+
+        let Some(level) = self.gzip_level else {
+            self.stats.bytes_sent.fetch_add(payload.len() as u64, Ordering::Relaxed);
+            return payload.into_bytes();
+        };
+
+        let mut encoder = GzEncoder::new(Vec::with_capacity(payload.len() / 4), GzCompression::new(level));
+        let body = match encoder.write_all(payload.as_bytes()).and_then(|_| encoder.finish()) {
+            Ok(b) => b,
+            Err(e) => {
+                // Writing to memory: should not happen
+                error!("Failed to gzip HEC payload: {}", e);
+                return payload.into_bytes();
+            }
+        };
+
+        if !payload.is_empty() {
+            let ratio = body.len() as f64 / payload.len() as f64;
+            // Moving average, so a single odd batch does not distort the estimate
+            self.compression_ratio = Some(match self.compression_ratio {
+                Some(r) => 0.7 * r + 0.3 * ratio,
+                None => ratio,
+            });
+        }
+        self.stats.bytes_sent.fetch_add(body.len() as u64, Ordering::Relaxed);
+        body
+    }
+
+    /// Size on the wire of `raw_len` bytes of events: the raw size, or with gzip the
+    /// compressed size estimated from the previous batches.
+    fn wire_size(&self, raw_len: usize) -> usize {
+        match (self.gzip_level, self.compression_ratio) {
+            (Some(_), Some(ratio)) => ((raw_len as f64 * ratio) as usize).max(raw_len / MAX_COMPRESSION_FACTOR),
+            // No estimate yet: the first batch is sized on its raw length
+            _ => raw_len,
+        }
     }
 
+    /// Headers of every HEC request (batches and ack polls).
     fn headers(&self) -> HeaderMap {
         let mut headers = HeaderMap::new();
         let token_value = format!("Splunk {}", self.token);
 
-        headers.insert(
-            "Authorization",
-            HeaderValue::from_str(&token_value).unwrap(),
-        );
+        // Sensitive: never printed by the HTTP client debug output
+        let mut auth = HeaderValue::from_str(&token_value).unwrap();
+        auth.set_sensitive(true);
+        headers.insert("Authorization", auth);
         headers.insert(
             "X-Splunk-Request-Channel",
-            HeaderValue::from_str(&Uuid::new_v4().to_string()).unwrap(),
+            HeaderValue::from_str(&self.channel).unwrap(),
         );
         headers
     }
 
-    /// Post a batch payload to HEC, retrying on 503 "Server is busy".
-    fn post_payload(&self, payload: &str) -> Result<(), reqwest::Error> {
-        let uri = self.server_uri();
-        let max_attempts = 5;
+    /// Headers of a batch post: the body is gzipped when compression is enabled.
+    fn payload_headers(&self) -> HeaderMap {
+        let mut headers = self.headers();
+        if self.gzip_level.is_some() {
+            headers.insert("Content-Encoding", HeaderValue::from_static("gzip"));
+        }
+        headers
+    }
 
-        for attempt in 0..max_attempts {
+    /// Post a batch payload to HEC, retrying network errors and transient statuses
+    /// (429, 5xx) according to the retry policy.
+    /// Each attempt goes to the endpoint picked by the pool, so a busy or unreachable
+    /// receiver is avoided by the next attempt.
+    fn post_payload(&self, events: &[String], body: &[u8]) -> PostOutcome {
+        let input_path = self.input_path();
+        let mut retries = Retries::new(&self.retry);
+
+        loop {
+            retries.attempts += 1;
+            let endpoint = self.endpoints.acquire();
+            let uri = format!("{}{}", self.endpoints.base_uri(endpoint.index), input_path);
             debug!(
                 "Posting to HEC URI: {} (attempt {}/{})",
                 uri,
-                attempt + 1,
-                max_attempts
+                retries.attempts,
+                retries.max_attempts()
             );
 
-            let resp = self
+            // Why this attempt failed: (HTTP status, reason, Retry-After)
+            let (status, reason, requested) = match self
                 .client
                 .post(&uri)
-                .headers(self.headers())
-                .body(payload.to_string())
-                .send()?;
+                .headers(self.payload_headers())
+                .body(body.to_vec())
+                .send()
+            {
+                Err(e) => {
+                    // Connection refused or reset, timeout, TLS error...
+                    self.endpoints.report_unreachable(endpoint.index, &e);
+                    // Fail over to another receiver without waiting
+                    if retries.attempts < retries.max_attempts() && self.endpoints.has_alternative(endpoint.index) {
+                        warn!("Error sending batch to {}: {}; trying another HEC endpoint", uri, e);
+                        continue;
+                    }
+                    (None, format!("network error: {}", describe_error(&e)), None)
+                }
+                Ok(resp) => {
+                    let status = resp.status();
+                    let requested = retry_after(resp.headers());
+                    let body_text = resp.text().unwrap_or_default();
+
+                    if status.is_success() {
+                        debug!("HEC status={} body={}", status, body_text);
+                        self.endpoints.report_success(endpoint.index);
+                        // {"text":"Success","code":0,"ackId":42}
+                        let ack_id = serde_json::from_str::<Value>(&body_text)
+                            .ok()
+                            .and_then(|v| v.get("ackId").and_then(|a| a.as_u64()));
+                        return PostOutcome::Accepted { ack_id, endpoint: endpoint.index };
+                    }
 
-            let status = resp.status();
-            let body_text = resp.text().unwrap_or_default();
+                    if !is_retryable_status(status.as_u16()) {
+                        // Any other HTTP error: the batch itself is refused, retrying would not help
+                        error!(
+                            "HEC error status={} body={}; batch refused",
+                            status, body_text
+                        );
+                        return PostOutcome::Rejected { status: status.as_u16(), body: body_text };
+                    }
 
-            if status.is_success() {
-                debug!("HEC status={} body={}", status, body_text);
-                return Ok(());
+                    // Splunk is overloaded ("Server is busy") or a gateway failed
+                    self.endpoints.report_busy(endpoint.index, status.as_u16());
+                    (Some(status.as_u16()), format!("status {} {}", status.as_u16(), body_text), requested)
+                }
+            };
+            drop(endpoint);
+
+            match retries.next_delay(requested) {
+                Some(delay) => {
+                    warn!(
+                        "HEC attempt {}/{} failed ({}); retrying in {:?}",
+                        retries.attempts,
+                        retries.max_attempts(),
+                        reason,
+                        delay
+                    );
+                    std::thread::sleep(delay);
+                }
+                None => {
+                    error!(
+                        "Giving up on a batch of {} events after {} attempts in {:?}: {}",
+                        events.len(),
+                        retries.attempts,
+                        retries.elapsed(),
+                        reason
+                    );
+                    return PostOutcome::Exhausted { status, reason };
+                }
             }
+        }
+    }
 
-            // Splunk is overloaded: "Server is busy"
-            if status.as_u16() == 503 && attempt + 1 < max_attempts {
-                error!(
-                    "HEC busy (503). body={}; will retry after backoff (attempt {}/{})",
-                    body_text,
-                    attempt + 1,
-                    max_attempts
+    /// Send a batch and, in ack mode, register its ackId as pending.
+    fn send_events(&mut self, events: Vec<String>, origins: Vec<EventOrigin>, resends: u32) {
+        let nb_events = events.len() as u64;
+
+        if let Some(ref limiter) = self.rate_limiter {
+            limiter.acquire(events.len(), events.iter().map(|e| e.len()).sum());
+        }
+
+        let body = self.encode_body(&events);
+
+        match self.post_payload(&events, &body) {
+            PostOutcome::Accepted { ack_id: Some(ack_id), endpoint } if self.use_ack => {
+                debug!("HEC batch of {} events pending ack {} on channel {}", nb_events, ack_id, self.channel);
+                self.pending_acks.push(PendingAck {
+                    ack_id,
+                    endpoint,
+                    events,
+                    origins,
+                    sent_at: Instant::now(),
+                    resends,
+                });
+            }
+            PostOutcome::Accepted { ack_id: None, .. } if self.use_ack => {
+                // Without an ackId the batch can never be confirmed: not delivered, not checkpointed
+                if resends >= MAX_ACK_RESENDS {
+                    error!(
+                        "HEC accepted a batch of {} events without an ackId {} times; is useACK enabled on the token?",
+                        nb_events,
+                        resends + 1
+                    );
+                    self.fail_batch(&events, &origins, None, "accepted without an ackId");
+                } else {
+                    warn!(
+                        "HEC accepted a batch without returning an ackId, re-sending it (resend {}/{})",
+                        resends + 1,
+                        MAX_ACK_RESENDS
+                    );
+                    self.send_events(events, origins, resends + 1);
+                }
+            }
+            PostOutcome::Accepted { .. } => {
+                self.stats.events_delivered.fetch_add(nb_events, Ordering::Relaxed);
+                mark_handled(&origins);
+            }
+            PostOutcome::Rejected { status, body } => match Self::invalid_event(&body, &events) {
+                Some(position) => self.isolate_invalid_events(events, origins, resends, position, status, &body),
+                None => self.fail_batch(&events, &origins, Some(status), &body),
+            },
+            PostOutcome::Exhausted { status, reason } => {
+                self.stats.batches_exhausted.fetch_add(1, Ordering::Relaxed);
+                self.fail_batch(&events, &origins, status, &reason);
+            }
+        }
+    }
+
+    /// Event that made HEC refuse a batch: Some(Some(n)) when Splunk gives its position
+    /// (`invalid-event-number`, the event is printed in debug), Some(None) when it does not,
+    /// None for other errors.
+    fn invalid_event(body_text: &str, events: &[String]) -> Option<Option<usize>> {
+        let err = serde_json::from_str::<Value>(body_text).ok()?;
+        let code = err.get("code").and_then(|v| v.as_i64())?;
+        if !INVALID_EVENT_CODES.contains(&code) {
+            return None;
+        }
+
+        let idx = err.get("invalid-event-number").and_then(|v| v.as_u64()).map(|n| n as usize);
+        match idx {
+            Some(idx) if idx < events.len() => {
+                let bad_event = &events[idx];
+                debug!(
+                    "\n==================== BAD SPLUNK EVENT (index {}) ====================\n{}\n====================================================================",
+                    idx, bad_event
                 );
-                // simple linear backoff: 500ms, 1s, 1.5s, 2s, ...
-                let backoff_ms = 500 * (attempt + 1) as u64;
-                std::thread::sleep(Duration::from_millis(backoff_ms));
-                continue;
+                // Also pretty-print JSON if valid
+                if let Ok(parsed) = serde_json::from_str::<Value>(bad_event) {
+                    debug!("\nPretty JSON:\n{}\n", serde_json::to_string_pretty(&parsed).unwrap_or_default());
+                }
+                Some(Some(idx))
+            }
+            Some(idx) => {
+                debug!("Splunk reported invalid event {}, but batch has only {} events!", idx, events.len());
+                Some(None)
             }
+            None => Some(None),
+        }
+    }
 
-            // Any other HTTP error or final 503 attempt: log and give up on this batch
-            error!(
-                "HEC error status={} body={}; giving up on this batch",
-                status, body_text
-            );
+    /// A batch refused because of an invalid event: quarantine that event and send the
+    /// rest again. Without `invalid-event-number`, the batch is bisected until the bad
+    /// events are alone.
+    fn isolate_invalid_events(
+        &mut self,
+        mut events: Vec<String>,
+        mut origins: Vec<EventOrigin>,
+        resends: u32,
+        position: Option<usize>,
+        status: u16,
+        body: &str,
+    ) {
+        if events.len() == 1 {
+            self.quarantine_event(events.remove(0), origins.remove(0), status, body);
+            return;
+        }
+
+        match position {
+            Some(n) => {
+                let event = events.remove(n);
+                let origin = origins.remove(n);
+                self.quarantine_event(event, origin, status, body);
+                // The rest of the batch may hold more invalid events
+                self.send_events(events, origins, resends);
+            }
+            None => {
+                let half = events.len() / 2;
+                debug!("HEC refused a batch of {} events without pointing the bad one; bisecting", events.len());
+                let tail_events = events.split_off(half);
+                let tail_origins = origins.split_off(half);
+                self.send_events(events, origins, resends);
+                self.send_events(tail_events, tail_origins, resends);
+            }
+        }
+    }
+
+    /// Persist a single event refused by HEC with its source file and line.
+    fn quarantine_event(&self, event: String, origin: EventOrigin, status: u16, body: &str) {
+        warn!(
+            "HEC refused the event of {} line {} ({}); quarantining it",
+            origin.sourcefile, origin.line, body
+        );
+        let (events, origins) = (std::slice::from_ref(&event), std::slice::from_ref(&origin));
+
+        match self.quarantine {
+            Some(ref quarantine) => {
+                if quarantine.write(events, origins, self.index.as_deref(), Some(status), body) {
+                    self.stats.events_quarantined.fetch_add(1, Ordering::Relaxed);
+                    mark_handled(origins);
+                }
+            }
+            None => self.fail_batch(events, origins, Some(status), body),
+        }
+    }
+
+    /// Count a batch as failed and persist it to the dead-letter queue (if any).
+    fn fail_batch(&self, events: &[String], origins: &[EventOrigin], status: Option<u16>, body: &str) {
+        self.stats.batches_failed.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(ref dlq) = self.dead_letters {
+            if dlq.write(events, origins, self.index.as_deref(), status, body) {
+                self.stats.batches_dead_lettered.fetch_add(1, Ordering::Relaxed);
+                // Persisted for --replay-dlq: a resumed run must not send it again
+                mark_handled(origins);
+            }
+        } else {
+            error!("Batch of {} events dropped (no dead-letter queue configured)", events.len());
+        }
+    }
 
-            // Attempt to parse Splunk error message and extract invalid event
-            if let Ok(err_json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                if let Some(code) = err_json.get("code").and_then(|v| v.as_i64()) {
-                    if code == 15 {
-                        if let Some(invalid_idx) = err_json
-                            .get("invalid-event-number")
-                            .and_then(|v| v.as_u64())
-                        {
-                            let idx = invalid_idx as usize;
-
-                            if idx < self.batch_events.len() {
-                                let bad_event = &self.batch_events[idx];
-                                
-                                debug!(
-                                    "\n==================== BAD SPLUNK EVENT (index {}) ====================\n{}\n====================================================================",
-                                    idx, bad_event
-                                );
-
-                                // Optional: Also pretty-print JSON if valid
-                                if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(bad_event) {
-                                    debug!(
-                                        "\nPretty JSON:\n{}\n",
-                                        serde_json::to_string_pretty(&parsed).unwrap()
-                                    );
-                                }
-                            } else {
-                                debug!("Splunk reported invalid event {}, but batch has only {} events!", idx, self.batch_events.len());
-                            }
-                        }
+    /// Ask one endpoint which of `ids` are indexed.
+    fn query_acks(&self, endpoint: usize, ids: &[u64]) -> Vec<u64> {
+        let uri = format!("{}/ack", self.endpoints.base_uri(endpoint));
+        let body = json!({ "acks": ids }).to_string();
+
+        // {"acks":{"0":true,"1":false}}
+        match self.client.post(&uri).headers(self.headers()).body(body).send() {
+            Ok(resp) if resp.status().is_success() => {
+                let text = resp.text().unwrap_or_default();
+                match serde_json::from_str::<Value>(&text) {
+                    Ok(v) => v
+                        .get("acks")
+                        .and_then(|a| a.as_object())
+                        .map(|m| {
+                            m.iter()
+                                .filter(|(_, ok)| ok.as_bool().unwrap_or(false))
+                                .filter_map(|(id, _)| id.parse().ok())
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    Err(e) => {
+                        warn!("Unexpected HEC ack response {}: {}", text, e);
+                        Vec::new()
                     }
                 }
             }
+            Ok(resp) => {
+                let status = resp.status();
+                warn!("HEC ack poll failed with status {}: {}", status, resp.text().unwrap_or_default());
+                Vec::new()
+            }
+            Err(e) => {
+                warn!("HEC ack poll failed (network error): {}", e);
+                self.endpoints.report_unreachable(endpoint, &e);
+                Vec::new()
+            }
+        }
+    }
 
-            return Ok(());
+    /// Ask Splunk which pending ackIds are indexed, then re-send batches
+    /// that stayed unacknowledged for longer than `ack_timeout`.
+    fn poll_acks(&mut self) {
+        if self.pending_acks.is_empty() {
+            return;
+        }
+
+        // ackIds are per receiver: ask each endpoint about the batches it accepted
+        let mut acked: HashSet<(usize, u64)> = HashSet::new();
+        let endpoints: BTreeSet<usize> = self.pending_acks.iter().map(|p| p.endpoint).collect();
+        for endpoint in endpoints {
+            let ids: Vec<u64> = self
+                .pending_acks
+                .iter()
+                .filter(|p| p.endpoint == endpoint)
+                .map(|p| p.ack_id)
+                .collect();
+            for ack_id in self.query_acks(endpoint, &ids) {
+                acked.insert((endpoint, ack_id));
+            }
+        }
 
+        let mut expired = Vec::new();
+        let mut still_pending = Vec::new();
+        for pending in self.pending_acks.drain(..) {
+            if acked.contains(&(pending.endpoint, pending.ack_id)) {
+                self.stats
+                    .events_delivered
+                    .fetch_add(pending.events.len() as u64, Ordering::Relaxed);
+                mark_handled(&pending.origins);
+            } else if pending.sent_at.elapsed() >= self.ack_timeout {
+                expired.push(pending);
+            } else {
+                still_pending.push(pending);
+            }
         }
+        self.pending_acks = still_pending;
 
-        Ok(())
+        for pending in expired {
+            if pending.resends >= MAX_ACK_RESENDS {
+                error!(
+                    "Batch of {} events never acked after {} resends (ackId {}); giving up on this batch",
+                    pending.events.len(),
+                    pending.resends,
+                    pending.ack_id
+                );
+                let reason = format!("not acknowledged after {} resends", pending.resends);
+                self.fail_batch(&pending.events, &pending.origins, None, &reason);
+                continue;
+            }
+            warn!(
+                "Batch with ackId {} not acked after {:?}, re-sending it (resend {}/{})",
+                pending.ack_id,
+                self.ack_timeout,
+                pending.resends + 1,
+                MAX_ACK_RESENDS
+            );
+            self.send_events(pending.events, pending.origins, pending.resends + 1);
+        }
+    }
+
+    /// Block until every pending batch is either acked or given up.
+    pub fn wait_for_acks(&mut self) {
+        self.poll_acks();
+        while !self.pending_acks.is_empty() {
+            std::thread::sleep(ACK_POLL_INTERVAL);
+            self.poll_acks();
+        }
+    }
+
+    /// Poll `endpoint` until `ack_id` is acked, or `ack_timeout` expires.
+    fn wait_for_ack(&self, endpoint: usize, ack_id: u64) -> bool {
+        let sent_at = Instant::now();
+        loop {
+            if self.query_acks(endpoint, &[ack_id]).contains(&ack_id) {
+                return true;
+            }
+            if sent_at.elapsed() >= self.ack_timeout {
+                warn!("Batch with ackId {} not acked after {:?}", ack_id, self.ack_timeout);
+                return false;
+            }
+            std::thread::sleep(ACK_POLL_INTERVAL);
+        }
+    }
+
+    /// Send a dead-lettered batch as a single request and, in ack mode, wait for its ack.
+    /// Returns whether the whole batch was delivered. A failed batch is neither retried
+    /// beyond the retry policy nor dead-lettered again: its file is kept for a later replay.
+    pub fn replay_batch(&mut self, events: Vec<String>) -> bool {
+        let nb_events = events.len() as u64;
+        if let Some(ref limiter) = self.rate_limiter {
+            limiter.acquire(events.len(), events.iter().map(|e| e.len()).sum());
+        }
+        self.stats.events_sent.fetch_add(nb_events, Ordering::Relaxed);
+
+        let body = self.encode_body(&events);
+        let delivered = match self.post_payload(&events, &body) {
+            PostOutcome::Accepted { ack_id: Some(ack_id), endpoint } if self.use_ack => {
+                self.wait_for_ack(endpoint, ack_id)
+            }
+            PostOutcome::Accepted { ack_id: None, .. } if self.use_ack => {
+                warn!("HEC accepted a batch without returning an ackId; is useACK enabled on the token?");
+                false
+            }
+            PostOutcome::Accepted { .. } => true,
+            PostOutcome::Rejected { .. } => false,
+            PostOutcome::Exhausted { .. } => {
+                self.stats.batches_exhausted.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        };
+
+        if delivered {
+            self.stats.events_delivered.fetch_add(nb_events, Ordering::Relaxed);
+        } else {
+            self.stats.batches_failed.fetch_add(1, Ordering::Relaxed);
+        }
+        delivered
     }
 
     /// Queue an event in the batch buffer (auto-flush on size).
-    pub fn batch_event(&mut self, mut payload: Value) {
+    pub fn batch_event(&mut self, mut payload: Value, origin: EventOrigin) {
         if self.input_type == "json" {
             if !payload.get("host").is_some() {
                 payload["host"] = Value::String(self.host.clone());
@@ -225,13 +741,14 @@ impl HttpEventCollector {
 
         let len = payload_str.len();
 
-        if self.current_byte_length + len > self.max_byte_length {
+        if self.wire_size(self.current_byte_length + len) > self.max_byte_length {
             debug!("Auto flush: existing batch too large, flushing now.");
             self.flush_batch();
         }
 
         self.current_byte_length += len;
         self.batch_events.push(payload_str);
+        self.batch_origins.push(origin);
     }
 
     /// Flush buffered batch events to Splunk.
@@ -240,15 +757,78 @@ impl HttpEventCollector {
             return;
         }
 
-        let payload = self.batch_events.join("");
-        debug!("Flushing {} bytes to Splunk HEC", payload.len());
+        let events = std::mem::take(&mut self.batch_events);
+        let origins = std::mem::take(&mut self.batch_origins);
+        self.current_byte_length = 0;
+        debug!("Flushing {} events to Splunk HEC", events.len());
+
+        self.stats
+            .events_sent
+            .fetch_add(events.len() as u64, Ordering::Relaxed);
+        self.send_events(events, origins, 0);
 
-        if let Err(e) = self.post_payload(&payload) {
-            // Network / client errors (DNS, TLS, timeout, etc.)
-            error!("Error sending batch to HEC (network error): {}", e);
+        if self.pending_acks.len() >= MAX_PENDING_ACKS {
+            self.poll_acks();
         }
+    }
 
-        self.batch_events.clear();
-        self.current_byte_length = 0;
+    /// Flush the buffer and, in ack mode, wait until all batches are acked.
+    pub fn finish(&mut self) {
+        self.flush_batch();
+        if self.use_ack {
+            self.wait_for_acks();
+        }
+    }
+
+    /// Log the delivery counters of this collector and all its forks.
+    pub fn log_stats(&self) {
+        let sent = self.stats.events_sent.load(Ordering::Relaxed);
+        let delivered = self.stats.events_delivered.load(Ordering::Relaxed);
+        let failed = self.stats.batches_failed.load(Ordering::Relaxed);
+        let dead_lettered = self.stats.batches_dead_lettered.load(Ordering::Relaxed);
+        let exhausted = self.stats.batches_exhausted.load(Ordering::Relaxed);
+        let how = if self.use_ack { "acknowledged by indexers" } else { "accepted by HEC" };
+        info!(
+            "HEC delivery: {} events sent, {} {}, {} batches failed ({} written to dead-letter queue)",
+            sent, delivered, how, failed, dead_lettered
+        );
+        let bytes_raw = self.stats.bytes_raw.load(Ordering::Relaxed);
+        let bytes_sent = self.stats.bytes_sent.load(Ordering::Relaxed);
+        if self.gzip_level.is_some() && bytes_raw > 0 {
+            info!(
+                "HEC payload: {:.1} MB sent, {:.1} MB before gzip ({:.0}%)",
+                bytes_sent as f64 / 1_048_576.0,
+                bytes_raw as f64 / 1_048_576.0,
+                bytes_sent as f64 * 100.0 / bytes_raw as f64
+            );
+        }
+        if exhausted > 0 {
+            warn!(
+                "{} of the failed batches ran out of retries (HEC unreachable or overloaded), {} were rejected by HEC",
+                exhausted,
+                failed.saturating_sub(exhausted)
+            );
+        }
+        if dead_lettered > 0
+            && let Some(ref dlq) = self.dead_letters
+        {
+            warn!(
+                "Failed batches are in {}; re-send them with --replay-dlq",
+                dlq.dir().display()
+            );
+        }
+        let quarantined = self.stats.events_quarantined.load(Ordering::Relaxed);
+        if quarantined > 0
+            && let Some(ref quarantine) = self.quarantine
+        {
+            warn!(
+                "{} events refused by HEC (invalid data) were quarantined in {}",
+                quarantined,
+                quarantine.dir().display()
+            );
+        }
+        if delivered + quarantined < sent {
+            warn!("{} events were sent but not confirmed as delivered", sent - delivered - quarantined);
+        }
     }
 }
@@ -0,0 +1,87 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Settings of the indexes created by json2splunk-rs (`index_settings:` section of the
+/// Splunk configuration). Unset fields keep the Splunk defaults.
+///
+///   frozen_time_period_secs : retention, events older than this are frozen (deleted by default)
+///   max_total_data_size_mb  : size cap of the index, oldest buckets are frozen first
+///   home_path / cold_path / thawed_path : bucket paths, e.g. "volume:hot/$_index_name/db"
+///   datatype                : "event" (default) or "metric"
+///   app                     : app namespace of the index (/servicesNS/nobody/<app>/data/indexes)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IndexSettings {
+    pub frozen_time_period_secs: Option<u64>,
+    pub max_total_data_size_mb: Option<u64>,
+    pub home_path: Option<String>,
+    pub cold_path: Option<String>,
+    pub thawed_path: Option<String>,
+    pub datatype: Option<String>,
+    pub app: Option<String>,
+}
+
+impl IndexSettings {
+    /// Check values Splunk would reject with a less readable error.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(ref datatype) = self.datatype
+            && datatype != "event"
+            && datatype != "metric"
+        {
+            return Err(format!("invalid datatype '{}': expected event or metric", datatype));
+        }
+        if let Some(ref app) = self.app
+            && (app.is_empty() || app.contains('/'))
+        {
+            return Err(format!("invalid app '{}'", app));
+        }
+        Ok(())
+    }
+
+    /// Configured settings, by their Splunk (indexes.conf) names.
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(v) = self.frozen_time_period_secs {
+            params.push(("frozenTimePeriodInSecs", v.to_string()));
+        }
+        if let Some(v) = self.max_total_data_size_mb {
+            params.push(("maxTotalDataSizeMB", v.to_string()));
+        }
+        if let Some(ref v) = self.home_path {
+            params.push(("homePath", v.clone()));
+        }
+        if let Some(ref v) = self.cold_path {
+            params.push(("coldPath", v.clone()));
+        }
+        if let Some(ref v) = self.thawed_path {
+            params.push(("thawedPath", v.clone()));
+        }
+        if let Some(ref v) = self.datatype {
+            params.push(("datatype", v.clone()));
+        }
+        params
+    }
+
+    /// REST path of the indexes collection, in the app namespace if one is set.
+    pub fn collection_path(&self) -> String {
+        match self.app {
+            Some(ref app) => format!("/servicesNS/nobody/{}/data/indexes", app),
+            None => "/services/data/indexes".to_string(),
+        }
+    }
+
+    /// Differences between the configured settings and the `content` of an existing index
+    /// (JSON output of /data/indexes/<name>): (setting, actual, configured).
+    pub fn drift(&self, content: &Value) -> Vec<(&'static str, String, String)> {
+        self.params()
+            .into_iter()
+            .filter_map(|(key, expected)| {
+                let actual = match content.get(key) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Null) | None => String::new(),
+                    Some(v) => v.to_string(),
+                };
+                (actual != expected).then_some((key, actual, expected))
+            })
+            .collect()
+    }
+}
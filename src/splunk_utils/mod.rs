@@ -1,2 +1,9 @@
+pub mod dead_letter;
+pub mod hec_endpoints;
 pub mod http_event_collector;
+pub mod index_settings;
+pub mod rate_limit;
+pub mod reconcile;
+pub mod retry;
 pub mod splunk_helper;
+pub mod tls;
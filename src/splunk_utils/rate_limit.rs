@@ -0,0 +1,87 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use log::info;
+
+/// Interval of the "achieved rate" log lines.
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Throughput cap shared by every HEC collector (all worker threads and files).
+///
+/// Each batch reserves a time slot long enough to keep the average under both
+/// limits (events/s and bytes/s of uncompressed event data), then waits for it.
+/// A batch is never split: with a low limit, the wait is spread between batches.
+pub struct RateLimiter {
+    max_eps: Option<f64>,
+    /// Bytes per second
+    max_bps: Option<f64>,
+    /// End of the last reserved slot
+    next_slot: Mutex<Instant>,
+    events: AtomicU64,
+    bytes: AtomicU64,
+    /// Time and counters of the last report
+    last_report: Mutex<(Instant, u64, u64)>,
+}
+
+impl RateLimiter {
+    /// `max_mbps` is in megabytes (1 MB = 1,000,000 bytes) per second.
+    pub fn new(max_eps: Option<f64>, max_mbps: Option<f64>) -> Self {
+        let now = Instant::now();
+        RateLimiter {
+            max_eps,
+            max_bps: max_mbps.map(|m| m * 1_000_000.0),
+            next_slot: Mutex::new(now),
+            events: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            last_report: Mutex::new((now, 0, 0)),
+        }
+    }
+
+    /// Block until a batch of `events` events and `bytes` bytes may be sent.
+    pub fn acquire(&self, events: usize, bytes: usize) {
+        let cost_eps = self.max_eps.map_or(0.0, |max| events as f64 / max);
+        let cost_bps = self.max_bps.map_or(0.0, |max| bytes as f64 / max);
+        let cost = Duration::from_secs_f64(cost_eps.max(cost_bps));
+
+        let wait = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            // An idle period is not saved up for a later burst
+            let start = (*next_slot).max(now);
+            *next_slot = start + cost;
+            start - now
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+
+        self.events.fetch_add(events as u64, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.report();
+    }
+
+    /// Log the rate achieved since the last report, at most every REPORT_INTERVAL.
+    fn report(&self) {
+        // Another thread is reporting
+        let Ok(mut last) = self.last_report.try_lock() else {
+            return;
+        };
+        let elapsed = last.0.elapsed();
+        if elapsed < REPORT_INTERVAL {
+            return;
+        }
+
+        let events = self.events.load(Ordering::Relaxed);
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let secs = elapsed.as_secs_f64();
+        info!(
+            "HEC throughput: {:.0} events/s, {:.2} MB/s (limits: {}, {})",
+            (events - last.1) as f64 / secs,
+            (bytes - last.2) as f64 / secs / 1_000_000.0,
+            self.max_eps.map_or("none".to_string(), |m| format!("{} events/s", m)),
+            self.max_bps.map_or("none".to_string(), |m| format!("{} MB/s", m / 1_000_000.0)),
+        );
+        *last = (Instant::now(), events, bytes);
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Result of the comparison of one file with what Splunk indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Ok,
+    /// Fewer events indexed than announced by the summary events
+    Short,
+    /// More events indexed than announced (duplicates, e.g. a file sent twice)
+    Excess,
+    /// Events but no summary event: the ingestion of the file never finished
+    NoSummary,
+    /// Nothing indexed for the file
+    Missing,
+}
+
+impl FileStatus {
+    /// Whether re-sending the file can fix it.
+    pub fn needs_resend(self) -> bool {
+        matches!(self, FileStatus::Short | FileStatus::NoSummary | FileStatus::Missing)
+    }
+}
+
+/// One line of the discrepancy report.
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub sourcefile: String,
+    /// expected_event_count of the summary events, minus the quarantined events (None without summary)
+    pub expected: Option<u64>,
+    pub indexed: u64,
+    /// Events refused by HEC and quarantined: never indexed, so not expected
+    pub quarantined: u64,
+    pub status: FileStatus,
+}
+
+/// Counts of one sourcefile in Splunk.
+#[derive(Debug, Default)]
+pub struct IndexedCounts {
+    /// Events, summary events excluded
+    pub indexed: u64,
+    /// Largest expected_event_count: a file sent twice has two summaries
+    pub expected: u64,
+    pub summaries: u64,
+    /// A summary comes from a --resume run, which only counts the events it sent
+    pub resumed: bool,
+}
+
+/// Sourcefiles per verification search, to keep the search string short.
+pub const FILES_PER_SEARCH: usize = 200;
+
+/// Quoted search term value: backslashes (Windows paths) and quotes escaped. A `*` stays a
+/// wildcard: the other sourcefiles it may match are left out by `reconcile`.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Search counting, per sourcefile, the events and the expected counts of the
+/// `ingestion_metadata` summary events. Every index is searched: a VRL script may route
+/// events of a file to any index (`%splunk.index`). The summary is recognized in _raw, so
+/// the search does not depend on the JSON extraction settings of each sourcetype.
+pub fn count_query(sourcefiles: &[String]) -> String {
+    let sourcefiles = sourcefiles
+        .iter()
+        .map(|s| format!("sourcefile={}", quote(s)))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+
+    format!(
+        "search index=* ({}) \
+         | eval j2s_meta=if(like(_raw, \"%\\\"event_type\\\":\\\"ingestion_metadata\\\"%\"), 1, 0) \
+         | eval j2s_expected=if(j2s_meta==1, tonumber(spath(_raw, \"expected_event_count\")), null()) \
+         | eval j2s_resumed=if(j2s_meta==1 AND isnotnull(spath(_raw, \"resumed_from_line\")), 1, 0) \
+         | stats sum(eval(1-j2s_meta)) as indexed max(j2s_expected) as expected sum(j2s_meta) as summaries \
+         max(j2s_resumed) as resumed by sourcefile",
+        sourcefiles
+    )
+}
+
+/// Search results are strings ("42"); sums may come back as "42.0".
+fn number(row: &Value, key: &str) -> u64 {
+    match row.get(key) {
+        Some(Value::String(s)) => s.parse::<f64>().map(|v| v as u64).unwrap_or(0),
+        Some(v) => v.as_f64().map(|v| v as u64).unwrap_or(0),
+        None => 0,
+    }
+}
+
+/// Rows of `count_query`, by sourcefile.
+pub fn parse_counts(rows: &[Value]) -> HashMap<String, IndexedCounts> {
+    rows.iter()
+        .filter_map(|row| {
+            let sourcefile = row.get("sourcefile")?.as_str()?.to_string();
+            let counts = IndexedCounts {
+                indexed: number(row, "indexed"),
+                expected: number(row, "expected"),
+                summaries: number(row, "summaries"),
+                resumed: number(row, "resumed") > 0,
+            };
+            Some((sourcefile, counts))
+        })
+        .collect()
+}
+
+/// Compare the files of the input with their counts in Splunk. `quarantined` holds the
+/// events of each file refused by HEC, which the summary events count but Splunk never indexed.
+pub fn reconcile(
+    sourcefiles: &[String],
+    counts: &HashMap<String, IndexedCounts>,
+    quarantined: &HashMap<String, u64>,
+) -> Vec<FileReport> {
+    sourcefiles
+        .iter()
+        .map(|sourcefile| {
+            let quarantined = quarantined.get(sourcefile).copied().unwrap_or(0);
+            let (expected, indexed, status) = match counts.get(sourcefile) {
+                None => (None, 0, FileStatus::Missing),
+                Some(c) if c.summaries == 0 => (None, c.indexed, FileStatus::NoSummary),
+                // The events sent before the interruption are not in the summary: only a lower bound
+                Some(c) if c.resumed => {
+                    let expected = c.expected.saturating_sub(quarantined);
+                    let status = if c.indexed >= expected { FileStatus::Ok } else { FileStatus::Short };
+                    (Some(expected), c.indexed, status)
+                }
+                Some(c) => {
+                    let expected = c.expected.saturating_sub(quarantined);
+                    let status = match c.indexed.cmp(&expected) {
+                        std::cmp::Ordering::Equal => FileStatus::Ok,
+                        std::cmp::Ordering::Less => FileStatus::Short,
+                        std::cmp::Ordering::Greater => FileStatus::Excess,
+                    };
+                    (Some(expected), c.indexed, status)
+                }
+            };
+            FileReport {
+                sourcefile: sourcefile.clone(),
+                expected,
+                indexed,
+                quarantined,
+                status,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_query_quotes_sourcefiles() {
+        let query = count_query(&[r"C:\cases\a.json".to_string(), r#"/tmp/b "1".json"#.to_string()]);
+        assert!(query.starts_with(r#"search index=* (sourcefile="C:\\cases\\a.json" OR sourcefile="/tmp/b \"1\".json") |"#), "{}", query);
+        assert!(query.ends_with("by sourcefile"));
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde::Deserialize;
+
+/// Retry policy of HEC posts (`retry:` section of the Splunk configuration).
+///
+/// Network errors (connection refused or reset, timeouts) and the status codes 429, 500,
+/// 502, 503 and 504 are retried with exponential backoff and full jitter. A `Retry-After`
+/// header sent by Splunk replaces the computed delay.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts per batch, the first one included
+    pub max_attempts: u32,
+    /// Backoff ceiling of the first retry
+    pub initial_backoff_ms: u64,
+    /// Upper bound of a single delay
+    pub max_backoff_ms: u64,
+    /// Time budget of a batch: no retry is scheduled past it
+    pub max_total_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 8,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            max_total_secs: 300,
+        }
+    }
+}
+
+/// Status codes worth retrying: throttling and transient server or gateway errors.
+pub fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 429 | 500 | 502 | 503 | 504)
+}
+
+/// Delay requested by a `Retry-After` header, in seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    // "Wed, 21 Oct 2015 07:28:00 GMT"
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Retry state of one batch.
+pub struct Retries<'a> {
+    policy: &'a RetryPolicy,
+    started: Instant,
+    /// Attempts made so far
+    pub attempts: u32,
+}
+
+impl<'a> Retries<'a> {
+    pub fn new(policy: &'a RetryPolicy) -> Self {
+        Retries { policy, started: Instant::now(), attempts: 0 }
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.policy.max_attempts.max(1)
+    }
+
+    /// Delay before the next attempt, or None when the attempts or the time budget are exhausted.
+    /// `requested` is the Retry-After of the last response, which takes precedence over the backoff.
+    pub fn next_delay(&self, requested: Option<Duration>) -> Option<Duration> {
+        if self.attempts >= self.max_attempts() {
+            return None;
+        }
+
+        let budget = Duration::from_secs(self.policy.max_total_secs);
+        let remaining = budget.checked_sub(self.started.elapsed()).filter(|r| !r.is_zero())?;
+
+        match requested {
+            // Splunk asks to wait longer than the budget allows
+            Some(delay) if delay > remaining => None,
+            Some(delay) => Some(delay),
+            None => {
+                // Full jitter: uniform in [0, min(max, initial * 2^n)], within the budget
+                let exp = self.attempts.saturating_sub(1).min(20);
+                let ceiling = self
+                    .policy
+                    .initial_backoff_ms
+                    .saturating_mul(1 << exp)
+                    .min(self.policy.max_backoff_ms);
+                Some(Duration::from_millis(fastrand::u64(0..=ceiling)).min(remaining))
+            }
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// Error message with its causes ("error sending request: connection refused").
+pub fn describe_error(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy_with(max_attempts: u32, max_total_secs: u64) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            max_total_secs,
+        }
+    }
+
+    fn retries(policy: &RetryPolicy, attempts: u32, elapsed: Duration) -> Retries<'_> {
+        Retries { policy, started: Instant::now() - elapsed, attempts }
+    }
+
+    #[test]
+    fn backoff_within_ceiling() {
+        let policy = policy_with(30, 300);
+        for attempts in 1..30 {
+            let ceiling = (100u64 << (attempts - 1).min(20)).min(1_000);
+            for _ in 0..50 {
+                let delay = retries(&policy, attempts, Duration::ZERO).next_delay(None).unwrap();
+                assert!(delay <= Duration::from_millis(ceiling), "attempt {}: {:?}", attempts, delay);
+            }
+        }
+    }
+
+    #[test]
+    fn attempts_exhausted() {
+        let policy = policy_with(3, 300);
+        assert!(retries(&policy, 2, Duration::ZERO).next_delay(None).is_some());
+        assert!(retries(&policy, 3, Duration::ZERO).next_delay(None).is_none());
+        assert!(retries(&policy, 3, Duration::ZERO).next_delay(Some(Duration::ZERO)).is_none());
+
+        // max_attempts: 0 still makes one attempt
+        let policy = policy_with(0, 300);
+        assert_eq!(retries(&policy, 0, Duration::ZERO).max_attempts(), 1);
+        assert!(retries(&policy, 1, Duration::ZERO).next_delay(None).is_none());
+    }
+
+    #[test]
+    fn retry_after_takes_precedence() {
+        let policy = policy_with(8, 300);
+        let r = retries(&policy, 1, Duration::ZERO);
+        // Above max_backoff_ms, but within the budget
+        assert_eq!(r.next_delay(Some(Duration::from_secs(60))), Some(Duration::from_secs(60)));
+        assert_eq!(r.next_delay(Some(Duration::ZERO)), Some(Duration::ZERO));
+        // Longer than the remaining budget
+        assert_eq!(r.next_delay(Some(Duration::from_secs(301))), None);
+        let late = retries(&policy, 1, Duration::from_secs(290));
+        assert_eq!(late.next_delay(Some(Duration::from_secs(20))), None);
+        assert_eq!(late.next_delay(Some(Duration::from_secs(5))), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn time_budget() {
+        let policy = policy_with(8, 10);
+        assert!(retries(&policy, 1, Duration::from_secs(10)).next_delay(None).is_none());
+        assert!(retries(&policy, 1, Duration::from_secs(11)).next_delay(None).is_none());
+        // The backoff is cut to what remains of the budget
+        let delay = retries(&policy, 5, Duration::from_millis(9_950)).next_delay(None).unwrap();
+        assert!(delay <= Duration::from_millis(50), "{:?}", delay);
+
+        let policy = policy_with(8, 0);
+        assert!(retries(&policy, 1, Duration::ZERO).next_delay(None).is_none());
+    }
+
+    #[test]
+    fn retry_after_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, " 120 ".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+        headers.insert(RETRY_AFTER, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+        headers.insert(RETRY_AFTER, "soon".parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn retryable_status() {
+        for status in [429, 500, 502, 503, 504] {
+            assert!(is_retryable_status(status));
+        }
+        for status in [200, 400, 401, 403, 404, 413, 501] {
+            assert!(!is_retryable_status(status));
+        }
+    }
+}
@@ -4,6 +4,14 @@ use std::time::Duration;
 use log::{error, info, warn};
 use reqwest::blocking::{Client, Response};
 use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::splunk_utils::index_settings::IndexSettings;
+
+/// Name of the HEC input (token) created and used by json2splunk-rs.
+pub const HEC_INPUT_NAME: &str = "json2splunk-rs";
+/// Searches scan whole indexes: the client timeout is far too short for them.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(1800);
 
 pub struct SplunkHelper {
     surl: String,
@@ -13,16 +21,11 @@ pub struct SplunkHelper {
 }
 
 impl SplunkHelper {
-    pub fn new(host: &str, user: &str, pass: &str, port: u16, ssl: bool) -> Self {
-        let protocol = "https";
+    /// `client` carries the TLS settings (certificate verification, CA bundle, client certificate).
+    pub fn new(host: &str, user: &str, pass: &str, port: u16, ssl: bool, client: Client) -> Self {
+        let protocol = if ssl { "https" } else { "http" };
         let surl = format!("{protocol}://{host}:{port}");
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(15))
-            .danger_accept_invalid_certs(!ssl)
-            .build()
-            .expect("Failed to build reqwest client");
-
         SplunkHelper { surl, suser: user.to_string(), spass: pass.to_string(), client }
     }
 
@@ -71,24 +74,90 @@ impl SplunkHelper {
         false
     }
 
-    pub fn create_index(&self, index: &str) -> bool {
-        let uri = format!("/services/data/indexes/{}", index);
+    /// Run a search through /services/search/jobs/export and return its result rows.
+    /// Unlike oneshot searches, export streams every result (no 100 rows cap).
+    pub fn search_export(&self, query: &str) -> Option<Vec<Value>> {
+        let url = format!("{}/services/search/jobs/export", self.surl);
+        let form = [("search", query), ("output_mode", "json")];
+
+        let resp = match self
+            .client
+            .post(&url)
+            .basic_auth(&self.suser, Some(&self.spass))
+            .timeout(SEARCH_TIMEOUT)
+            .form(&form)
+            .send()
+        {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Search request error on {}: {}", url, e);
+                return None;
+            }
+        };
+
+        let status = resp.status();
+        let body = resp.text().unwrap_or_default();
+        if !status.is_success() {
+            error!("Search failed ({}): {}", status, body);
+            return None;
+        }
+
+        // One JSON object per line: {"preview":false,"result":{...}} or {"messages":[...]}
+        let mut rows = Vec::new();
+        for line in body.lines().filter(|l| !l.trim().is_empty()) {
+            let obj: Value = match serde_json::from_str(line) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("Unexpected search output line ({}): {}", e, line);
+                    return None;
+                }
+            };
+            if let Some(messages) = obj.get("messages").and_then(|m| m.as_array()) {
+                for m in messages {
+                    if m.get("type").and_then(|t| t.as_str()) == Some("ERROR") {
+                        error!("Search error: {}", m.get("text").and_then(|t| t.as_str()).unwrap_or_default());
+                        return None;
+                    }
+                }
+            }
+            if obj.get("preview").and_then(|p| p.as_bool()) == Some(true) {
+                continue;
+            }
+            if let Some(result) = obj.get("result") {
+                rows.push(result.clone());
+            }
+        }
+
+        Some(rows)
+    }
+
+    /// Create the index with `settings`, or check an existing one against them.
+    /// Settings that differ on an existing index are reported, not changed.
+    pub fn create_index(&self, index: &str, settings: &IndexSettings) -> bool {
+        let collection = settings.collection_path();
+        let uri = format!("{}/{}?output_mode=json", collection, index);
 
         if let Some(resp) = self.request(reqwest::Method::GET, &uri, None) {
             if resp.status().is_success() {
                 info!("Index {} already exists.", index);
+                let body: Value = serde_json::from_str(&resp.text().unwrap_or_default()).unwrap_or_default();
+                self.report_index_drift(index, settings, &body);
                 return true;
             }
         }
 
+        let params = settings.params();
         let mut form = HashMap::new();
         form.insert("name", index);
+        for (key, value) in &params {
+            form.insert(key, value.as_str());
+        }
 
         if let Some(resp) =
-            self.request(reqwest::Method::POST, "/services/data/indexes", Some(&form))
+            self.request(reqwest::Method::POST, &collection, Some(&form))
         {
             if resp.status().is_success() {
-                info!("Index {} created successfully.", index);
+                info!("Index {} created successfully {:?}.", index, params);
                 return true;
             }
             let status = resp.status();
@@ -99,13 +168,37 @@ impl SplunkHelper {
         false
     }
 
-    fn extract_token(body: &str) -> Option<String> {
-        let start_tag = r#"<s:key name="token">"#;
-        let start = body.find(start_tag)? + start_tag.len();
+    /// Warn about each configured setting an existing index does not have.
+    fn report_index_drift(&self, index: &str, settings: &IndexSettings, body: &Value) {
+        let content = match body.pointer("/entry/0/content") {
+            Some(c) => c,
+            None => {
+                if !settings.params().is_empty() {
+                    warn!("Cannot read the settings of index {}; drift not checked", index);
+                }
+                return;
+            }
+        };
+
+        for (key, actual, expected) in settings.drift(content) {
+            warn!(
+                "Index {} drift: {} is '{}', configured '{}' (existing indexes are not modified)",
+                index, key, actual, expected
+            );
+        }
+    }
+
+    fn extract_key(body: &str, key: &str) -> Option<String> {
+        let start_tag = format!(r#"<s:key name="{}">"#, key);
+        let start = body.find(&start_tag)? + start_tag.len();
         let end = body[start..].find("</s:key>")? + start;
         Some(body[start..end].trim().to_string())
     }
 
+    fn extract_token(body: &str) -> Option<String> {
+        Self::extract_key(body, "token")
+    }
+
     fn extract_indexes(body: &str) -> Vec<String> {
         let mut out = vec![];
 
@@ -136,9 +229,13 @@ impl SplunkHelper {
         out
     }
 
-    fn get_existing_token(&self, name: &str) -> Option<String> {
+    /// Token of an existing HEC input, Ok(None) when there is none. An input that exists but
+    /// cannot be switched to useACK is an error: it must not be created again.
+    fn get_existing_token(&self, name: &str, use_ack: bool) -> Result<Option<String>, String> {
         let uri = format!("/services/data/inputs/http/{}", name);
-        let resp = self.request(reqwest::Method::GET, &uri, None)?;
+        let Some(resp) = self.request(reqwest::Method::GET, &uri, None) else {
+            return Ok(None);
+        };
         let status = resp.status();
         let body = resp.text().unwrap_or_default();
 
@@ -147,23 +244,58 @@ impl SplunkHelper {
                 "No existing HEC input '{}' (status {}). Will probably need to create it.",
                 name, status
             );
-            return None;
+            return Ok(None);
         }
 
-        let token = Self::extract_token(&body)?;
+        let Some(token) = Self::extract_token(&body) else {
+            return Ok(None);
+        };
         info!(
             "Found existing HEC input '{}' (token begins with {}).",
             name,
             &token[..8.min(token.len())]
         );
-        Some(token)
+
+        let ack_enabled = Self::extract_key(&body, "useACK")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if use_ack && !ack_enabled && !self.enable_ack(name) {
+            return Err(format!(
+                "HEC input '{}' exists but useACK could not be enabled on it: enable it in Splunk or set use_ack: false",
+                name
+            ));
+        }
+        Ok(Some(token))
+    }
+
+    /// Turn on indexer acknowledgement on an existing HEC input.
+    fn enable_ack(&self, name: &str) -> bool {
+        info!("Enabling indexer acknowledgement (useACK) on HEC input '{}'…", name);
+
+        let uri = format!("/services/data/inputs/http/{}", name);
+        let mut form = HashMap::new();
+        form.insert("useACK", "1");
+
+        match self.request(reqwest::Method::POST, &uri, Some(&form)) {
+            Some(resp) if resp.status().is_success() => true,
+            Some(resp) => {
+                let status = resp.status();
+                let body = resp.text().unwrap_or_default();
+                error!("Failed to enable useACK on input {} ({}): {}", name, status, body);
+                false
+            }
+            None => false,
+        }
     }
 
-    fn create_new_token(&self, name: &str) -> Option<String> {
-        info!("Creating new HEC input '{}'…", name);
+    fn create_new_token(&self, name: &str, use_ack: bool) -> Option<String> {
+        info!("Creating new HEC input '{}' (useACK={})…", name, use_ack);
 
         let mut form = HashMap::new();
         form.insert("name", name);
+        if use_ack {
+            form.insert("useACK", "1");
+        }
 
         let resp = self.request(
             reqwest::Method::POST,
@@ -259,11 +391,18 @@ impl SplunkHelper {
         }
     }
 
-    pub fn ensure_hec_token(&self, index: &str) -> Option<String> {
-        let name = "json2splunk-rs";
+    pub fn ensure_hec_token(&self, index: &str, use_ack: bool) -> Option<String> {
+        let name = HEC_INPUT_NAME;
 
         // 1) Try existing input
-        if let Some(token) = self.get_existing_token(name) {
+        let existing = match self.get_existing_token(name, use_ack) {
+            Ok(existing) => existing,
+            Err(e) => {
+                error!("{}", e);
+                return None;
+            }
+        };
+        if let Some(token) = existing {
             info!(
                 "Using existing HEC input '{}' for index '{}'.",
                 name, index
@@ -284,7 +423,7 @@ impl SplunkHelper {
         ); 
 
         // 2) Create new input
-        let token = self.create_new_token(name)?;
+        let token = self.create_new_token(name, use_ack)?;
         if !self.register_index_to_token(name, index) {
             error!(
                 "Failed to associate index {} with new HEC input {}",
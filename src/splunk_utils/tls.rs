@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::PathBuf;
+
+use reqwest::blocking::ClientBuilder;
+use reqwest::{Certificate, Identity};
+use serde::Deserialize;
+
+/// TLS settings of the Splunk configuration, shared by the management and HEC clients.
+///
+///   verify      : check the server certificate (default: true; false is an explicit opt-out)
+///   ca_file     : PEM bundle of CAs trusted in addition to the system roots
+///   client_cert : PEM client certificate for mTLS (may also hold the private key)
+///   client_key  : PEM private key of client_cert
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TlsSettings {
+    pub verify: Option<bool>,
+    pub ca_file: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+impl TlsSettings {
+    /// Whether server certificates are verified: always, unless `verify: false` is set.
+    pub fn verify(&self) -> bool {
+        self.verify.unwrap_or(true)
+    }
+
+    /// Apply the settings to a client builder.
+    /// Returns a readable error when a certificate or key file cannot be loaded.
+    pub fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder, String> {
+        // PEM identities are rustls identities: the native-tls backend (also compiled in) rejects them
+        builder = builder.use_rustls_tls().danger_accept_invalid_certs(!self.verify());
+
+        if let Some(ref ca_file) = self.ca_file {
+            let pem = fs::read(ca_file).map_err(|e| format!("cannot read ca_file {:?}: {}", ca_file, e))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("invalid ca_file {:?}: {}", ca_file, e))?;
+            if certs.is_empty() {
+                return Err(format!("no certificate found in ca_file {:?}", ca_file));
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert_file), key_file) => {
+                // rustls expects the certificate chain and the key in a single PEM buffer
+                let mut pem = fs::read(cert_file)
+                    .map_err(|e| format!("cannot read client_cert {:?}: {}", cert_file, e))?;
+                if let Some(key_file) = key_file {
+                    let key = fs::read(key_file)
+                        .map_err(|e| format!("cannot read client_key {:?}: {}", key_file, e))?;
+                    pem.push(b'\n');
+                    pem.extend_from_slice(&key);
+                }
+                let identity = Identity::from_pem(&pem)
+                    .map_err(|e| format!("invalid client certificate {:?}: {}", cert_file, e))?;
+                builder = builder.identity(identity);
+            }
+            (None, Some(_)) => return Err("client_key is set without client_cert".to_string()),
+            (None, None) => {}
+        }
+
+        Ok(builder)
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crossbeam_channel::Receiver;
+use sevenz_rust2::{ArchiveReader, Password};
+
+use crate::utils::compression::{inner_extension, open_decompressed};
+
+/// Separator between an archive path and the path of a member inside it:
+///   collection.zip!/Windows/System32/winevt/Logs/Security.jsonl
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+/// Size of the chunks streamed from an archive member to the pipelines.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Archive containers whose members are matched and ingested like regular files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    /// Plain or compressed tar (.tar, .tar.gz, .tgz, .tar.zst, ...)
+    Tar,
+    SevenZip,
+}
+
+impl ArchiveKind {
+    /// Archive type from the file name, None for regular files.
+    pub fn from_path(path: &Path) -> Option<ArchiveKind> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match ext.as_str() {
+            "zip" => Some(ArchiveKind::Zip),
+            "7z" => Some(ArchiveKind::SevenZip),
+            "tar" | "tgz" | "tbz2" | "txz" | "tzst" => Some(ArchiveKind::Tar),
+            // Compressed tar: "a.tar.gz" has the inner extension "tar"
+            _ if inner_extension(path) == "tar" => Some(ArchiveKind::Tar),
+            _ => None,
+        }
+    }
+}
+
+/// A file stored in an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveMember {
+    /// Archive on disk
+    pub archive: PathBuf,
+    pub kind: ArchiveKind,
+    /// Path of the member inside the archive
+    pub name: String,
+}
+
+/// Virtual path of a member: "<archive>!/<inner path>".
+/// Used for pattern matching, `sourcefile` and the normalize-test outputs.
+/// Leading "/" and "./" are dropped: tar members are often stored as "./dir/file", the
+/// same file in a zip as "dir/file", and both must get the same path.
+pub fn member_path(archive: &Path, name: &str) -> PathBuf {
+    let mut name = name;
+    while let Some(rest) = name.strip_prefix("./").or_else(|| name.strip_prefix('/')) {
+        name = rest;
+    }
+    PathBuf::from(format!("{}{}{}", archive.display(), MEMBER_SEPARATOR, name))
+}
+
+fn sevenz_error(e: sevenz_rust2::Error) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+/// List the regular files of an archive, in archive order.
+pub fn list_members(path: &Path, kind: ArchiveKind) -> io::Result<Vec<String>> {
+    let mut members = Vec::new();
+
+    match kind {
+        ArchiveKind::Zip => {
+            let zip = zip::ZipArchive::new(BufReader::new(File::open(path)?))?;
+            for name in zip.file_names() {
+                let name = name?;
+                if !name.ends_with('/') {
+                    members.push(name.to_string());
+                }
+            }
+        }
+        ArchiveKind::Tar => {
+            let mut tar = tar::Archive::new(open_decompressed(path)?);
+            for entry in tar.entries()? {
+                let entry = entry?;
+                if entry.header().entry_type().is_file() {
+                    members.push(entry.path()?.to_string_lossy().to_string());
+                }
+            }
+        }
+        ArchiveKind::SevenZip => {
+            let archive = sevenz_rust2::Archive::open(path).map_err(sevenz_error)?;
+            for entry in &archive.files {
+                if !entry.is_directory() {
+                    members.push(entry.name().to_string());
+                }
+            }
+        }
+    }
+
+    Ok(members)
+}
+
+/// Read the archive sequentially and call `on_member` for every member listed in `wanted`.
+///
+/// The callback receives the member content as a reader; what it does not consume is skipped.
+/// Archives are read in a single pass, so compressed tar and solid 7z archives are
+/// decompressed only once.
+pub fn stream_members(
+    path: &Path,
+    kind: ArchiveKind,
+    wanted: &HashSet<&str>,
+    mut on_member: impl FnMut(&str, &mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(BufReader::new(File::open(path)?))?;
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i)?;
+                let name = file.name()?.to_string();
+                if wanted.contains(name.as_str()) {
+                    on_member(&name, &mut file)?;
+                }
+            }
+        }
+        ArchiveKind::Tar => {
+            let mut tar = tar::Archive::new(open_decompressed(path)?);
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                let name = entry.path()?.to_string_lossy().to_string();
+                if wanted.contains(name.as_str()) {
+                    on_member(&name, &mut entry)?;
+                }
+            }
+        }
+        ArchiveKind::SevenZip => {
+            let mut reader = ArchiveReader::open(path, Password::empty()).map_err(sevenz_error)?;
+            reader
+                .for_each_entries(|entry, data| {
+                    if wanted.contains(entry.name()) {
+                        on_member(entry.name(), data)?;
+                    }
+                    // 7z entries share the block decoder: the next entry starts where this one ends
+                    io::copy(data, &mut io::sink())?;
+                    Ok(true)
+                })
+                .map_err(sevenz_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy a member to a channel in chunks. Returns false if the receiver was dropped
+/// (member skipped by the pipeline).
+pub fn send_member(data: &mut dyn Read, tx: &crossbeam_channel::Sender<io::Result<Vec<u8>>>) -> bool {
+    loop {
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        match data.read(&mut chunk) {
+            Ok(0) => return true,
+            Ok(n) => {
+                chunk.truncate(n);
+                if tx.send(Ok(chunk)).is_err() {
+                    return false;
+                }
+            }
+            Err(e) => {
+                let _ = tx.send(Err(e));
+                return false;
+            }
+        }
+    }
+}
+
+/// Reader over the chunks of an archive member streamed by the archive reader thread.
+/// A read error on the archive side is returned by `read`, so a truncated member is never
+/// reported as completely read.
+pub struct ChannelReader {
+    rx: Receiver<io::Result<Vec<u8>>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    pub fn new(rx: Receiver<io::Result<Vec<u8>>>) -> Self {
+        ChannelReader { rx, buf: Vec::new(), pos: 0 }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.buf.len() {
+            match self.rx.recv() {
+                Ok(Ok(chunk)) => {
+                    self.buf = chunk;
+                    self.pos = 0;
+                }
+                Ok(Err(e)) => return Err(e),
+                // Sender dropped: end of member
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let n = (self.buf.len() - self.pos).min(out.len());
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn member_path_drops_leading_dot_slash() {
+        let archive = Path::new("/cases/case.tar");
+        let expected = PathBuf::from("/cases/case.tar!/dir/file.json");
+        for name in ["dir/file.json", "./dir/file.json", "/dir/file.json", "././dir/file.json", ".//dir/file.json"] {
+            assert_eq!(member_path(archive, name), expected, "{}", name);
+        }
+        // Only a leading "./" is a current directory
+        assert_eq!(member_path(archive, "dir/./file.json"), PathBuf::from("/cases/case.tar!/dir/./file.json"));
+        assert_eq!(member_path(archive, ".hidden/file.json"), PathBuf::from("/cases/case.tar!/.hidden/file.json"));
+        assert_eq!(member_path(archive, "../file.json"), PathBuf::from("/cases/case.tar!/../file.json"));
+    }
+
+    #[test]
+    fn tar_member_with_dot_slash() {
+        let path = std::env::temp_dir().join(format!("json2splunk-archive-{}.tar", std::process::id()));
+        {
+            // tar::Builder::append_data would normalize the "./": set the raw header name
+            let mut builder = tar::Builder::new(File::create(&path).unwrap());
+            let data = b"{}\n";
+            let mut header = tar::Header::new_ustar();
+            header.as_ustar_mut().unwrap().name[..15].copy_from_slice(b"./dir/file.json");
+            header.set_size(data.len() as u64);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, &data[..]).unwrap();
+            builder.finish().unwrap();
+        }
+
+        let members = list_members(&path, ArchiveKind::Tar);
+        let _ = std::fs::remove_file(&path);
+        let members = members.unwrap();
+        assert_eq!(members, ["./dir/file.json"]);
+        assert_eq!(
+            member_path(Path::new("case.tar"), &members[0]),
+            member_path(Path::new("case.tar"), "dir/file.json")
+        );
+    }
+}
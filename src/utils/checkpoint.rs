@@ -0,0 +1,245 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::utils::hash_path;
+
+/// Progress is written to the journal every CHECKPOINT_EVERY delivered lines.
+const CHECKPOINT_EVERY: usize = 10_000;
+
+/// One line of the checkpoint journal (JSONL, append-only, last entry per key wins).
+///   {"key":"<hash>:<size>:<mtime>","file":"/path","line":12000}
+///   {"key":"<hash>:<size>:<mtime>","file":"/path","line":48213,"done":true}
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    key: String,
+    file: String,
+    line: usize,
+    #[serde(default)]
+    done: bool,
+}
+
+/// Journal of completed files and of the last delivered line (or CSV row) of partially
+/// processed files, used by --resume to skip data already sent to Splunk.
+///
+/// A file is identified by hash_path + size + mtime, so a file modified since the
+/// previous run is processed again from the start.
+pub struct CheckpointJournal {
+    path: PathBuf,
+    writer: Mutex<File>,
+    /// State loaded from the previous run (empty unless resuming)
+    previous: HashMap<String, JournalEntry>,
+}
+
+impl CheckpointJournal {
+    /// Open the journal. When `resume` is false the previous journal is discarded.
+    pub fn open(path: &Path, resume: bool) -> io::Result<Self> {
+        let mut previous = HashMap::new();
+
+        if resume && path.exists() {
+            let reader = BufReader::new(File::open(path)?);
+            for (i, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<JournalEntry>(&line) {
+                    Ok(entry) => {
+                        previous.insert(entry.key.clone(), entry);
+                    }
+                    // A crash can leave a truncated last line
+                    Err(e) => warn!("Ignoring invalid checkpoint entry {}:{}: {}", path.display(), i + 1, e),
+                }
+            }
+            let done = previous.values().filter(|e| e.done).count();
+            info!(
+                "Resuming from checkpoint journal {}: {} files completed, {} partially processed",
+                path.display(),
+                done,
+                previous.len() - done
+            );
+        } else if resume {
+            warn!("No checkpoint journal at {}, starting from scratch", path.display());
+        }
+
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(path)?;
+
+        Ok(CheckpointJournal {
+            path: path.to_path_buf(),
+            writer: Mutex::new(file),
+            previous,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Journal key of a file: hash_path + size + mtime.
+    /// For archive members, `on_disk` is the archive and `path` the "<archive>!/<member>" path.
+    fn file_key(path: &Path, on_disk: &Path) -> Option<String> {
+        let meta = fs::metadata(on_disk).ok()?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        Some(format!("{:016x}:{}:{}", hash_path(path), meta.len(), mtime))
+    }
+
+    fn append(&self, entry: &JournalEntry) {
+        let mut line = match serde_json::to_string(entry) {
+            Ok(l) => l,
+            Err(_) => return,
+        };
+        line.push('\n');
+
+        let mut w = self.writer.lock().unwrap();
+        if let Err(e) = w.write_all(line.as_bytes()).and_then(|_| w.flush()) {
+            warn!("Failed to write checkpoint journal {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Start tracking a file. Returns None if the file cannot be identified (no metadata).
+    pub fn track(self: &Arc<Self>, path: &Path, on_disk: &Path) -> Option<Arc<FileProgress>> {
+        let key = Self::file_key(path, on_disk)?;
+        let (start_line, done) = match self.previous.get(&key) {
+            Some(entry) => (entry.line, entry.done),
+            None => (0, false),
+        };
+
+        Some(Arc::new(FileProgress {
+            journal: Arc::clone(self),
+            key,
+            file: path.to_string_lossy().to_string(),
+            start_line,
+            already_done: done,
+            state: Mutex::new(ProgressState {
+                watermark: start_line,
+                completed: BTreeSet::new(),
+                total: None,
+                last_written: start_line,
+                done,
+            }),
+        }))
+    }
+}
+
+struct ProgressState {
+    /// Every line <= watermark has been delivered (or deliberately skipped)
+    watermark: usize,
+    /// Lines delivered out of order, above the watermark
+    completed: BTreeSet<usize>,
+    /// Number of lines of the file, known once the reader reached EOF
+    total: Option<usize>,
+    last_written: usize,
+    done: bool,
+}
+
+/// Delivery progress of one file. Lines are completed out of order by the workers
+/// (or by HEC once their batch is delivered); the journal only records the
+/// contiguous prefix of completed lines.
+pub struct FileProgress {
+    journal: Arc<CheckpointJournal>,
+    key: String,
+    file: String,
+    start_line: usize,
+    already_done: bool,
+    state: Mutex<ProgressState>,
+}
+
+impl FileProgress {
+    /// File fully processed by a previous run
+    pub fn is_done(&self) -> bool {
+        self.already_done
+    }
+
+    /// Number of lines (or CSV rows) already delivered by a previous run
+    pub fn start_line(&self) -> usize {
+        self.start_line
+    }
+
+    /// Mark a line as handled: delivered to Splunk, dead-lettered, or intentionally skipped.
+    pub fn complete(&self, line: usize) {
+        let mut guard = self.state.lock().unwrap();
+        let st = &mut *guard;
+        if line <= st.watermark {
+            return;
+        }
+        st.completed.insert(line);
+        while st.completed.remove(&(st.watermark + 1)) {
+            st.watermark += 1;
+        }
+        self.checkpoint(st);
+    }
+
+    /// Called by the reader at EOF with the number of the last line read.
+    pub fn finish_reading(&self, total_lines: usize) {
+        let mut st = self.state.lock().unwrap();
+        st.total = Some(total_lines);
+        self.checkpoint(&mut st);
+    }
+
+    fn checkpoint(&self, st: &mut ProgressState) {
+        if st.done {
+            return;
+        }
+
+        if let Some(total) = st.total
+            && st.watermark >= total
+        {
+            st.done = true;
+            debug!("Checkpoint: {} completed ({} lines)", self.file, total);
+            self.journal.append(&JournalEntry {
+                key: self.key.clone(),
+                file: self.file.clone(),
+                line: st.watermark,
+                done: true,
+            });
+            return;
+        }
+
+        if st.watermark >= st.last_written + CHECKPOINT_EVERY {
+            st.last_written = st.watermark;
+            self.journal.append(&JournalEntry {
+                key: self.key.clone(),
+                file: self.file.clone(),
+                line: st.watermark,
+                done: false,
+            });
+        }
+    }
+}
+
+impl Drop for FileProgress {
+    /// Record where an unfinished file stopped (e.g. batches that could not be delivered).
+    fn drop(&mut self) {
+        let st = self.state.lock().unwrap();
+        if !st.done && st.watermark > st.last_written {
+            self.journal.append(&JournalEntry {
+                key: self.key.clone(),
+                file: self.file.clone(),
+                line: st.watermark,
+                done: false,
+            });
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use log::debug;
+use xz2::read::XzDecoder;
+
+/// Compression formats read transparently by the ingestion pipelines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// Compression implied by a file extension (without the dot).
+    pub fn from_extension(ext: &str) -> Compression {
+        match ext.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Compression::Gzip,
+            "zst" | "zstd" => Compression::Zstd,
+            "bz2" => Compression::Bzip2,
+            "xz" => Compression::Xz,
+            _ => Compression::None,
+        }
+    }
+
+    /// Compression identified by the magic bytes at the start of a file.
+    pub fn from_magic(head: &[u8]) -> Compression {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if head.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// File name without its compression extension: "evtx.jsonl.gz" → "evtx.jsonl".
+/// Names without a compression extension are returned unchanged.
+pub fn inner_file_name(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && Compression::from_extension(ext) != Compression::None => {
+            stem.to_string()
+        }
+        _ => name,
+    }
+}
+
+/// Extension of the inner (decompressed) file, lowercased: "a.JSONL.gz" → "jsonl".
+pub fn inner_extension(path: &Path) -> String {
+    Path::new(&inner_file_name(path))
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Open a file for reading, decompressing it on the fly if needed.
+pub fn open_decompressed(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    decompress(File::open(path)?, path)
+}
+
+/// Wrap a reader with the decoder matching its content.
+/// The compression is identified by magic bytes first, then by the extension of `path`
+/// (e.g. an empty `.jsonl.gz`).
+pub fn decompress<R: Read + Send + 'static>(reader: R, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    let mut reader = BufReader::new(reader);
+
+    let head = reader.fill_buf()?;
+    let compression = match Compression::from_magic(head) {
+        Compression::None if head.len() < 6 => path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(Compression::from_extension)
+            .unwrap_or(Compression::None),
+        c => c,
+    };
+
+    if compression != Compression::None {
+        debug!("Reading {:?} as {:?} compressed", path, compression);
+    }
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(reader)),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+    })
+}
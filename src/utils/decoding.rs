@@ -0,0 +1,188 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use encoding_rs::{Decoder, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use log::{info, warn};
+
+use crate::utils::utils::LossyUtf8Reader;
+
+/// Size of the block sniffed by `encoding: auto`.
+const SNIFF_SIZE: usize = 64 * 1024;
+
+/// Streaming charset decoder: reads bytes in any encoding supported by `encoding_rs`
+/// and exposes them as UTF-8.
+///
+/// The decoder is stateful, so characters split across reads are decoded correctly.
+/// A BOM at the start of the stream selects the encoding and is removed.
+/// Malformed sequences are replaced by U+FFFD.
+pub struct DecodingReader<R: Read> {
+    inner: R,
+    decoder: Decoder,
+    in_buf: Vec<u8>,
+    out_buf: String,
+    out_pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> DecodingReader<R> {
+    pub fn new(inner: R, encoding: &'static Encoding) -> Self {
+        Self {
+            inner,
+            decoder: encoding.new_decoder(),
+            in_buf: vec![0u8; 8192], // chunk size (8 KiB)
+            out_buf: String::new(),
+            out_pos: 0,
+            eof: false,
+        }
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            // Serve decoded bytes first
+            if self.out_pos < self.out_buf.len() {
+                let bytes = self.out_buf.as_bytes();
+                let to_copy = (bytes.len() - self.out_pos).min(out.len());
+                out[..to_copy].copy_from_slice(&bytes[self.out_pos..self.out_pos + to_copy]);
+                self.out_pos += to_copy;
+                return Ok(to_copy);
+            }
+
+            if self.eof {
+                return Ok(0);
+            }
+
+            // Decode the next chunk; n == 0 flushes a sequence left incomplete at EOF
+            let n = self.inner.read(&mut self.in_buf)?;
+            let last = n == 0;
+            self.eof = last;
+
+            self.out_buf.clear();
+            self.out_pos = 0;
+
+            let mut src = &self.in_buf[..n];
+            loop {
+                let needed = self
+                    .decoder
+                    .max_utf8_buffer_length(src.len())
+                    .unwrap_or(src.len() * 3 + 16);
+                self.out_buf.reserve(needed);
+
+                let (result, read, _) = self.decoder.decode_to_string(src, &mut self.out_buf, last);
+                src = &src[read..];
+                if let encoding_rs::CoderResult::InputEmpty = result {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Encoding named by a BOM at the start of `head`.
+fn sniff_bom(head: &[u8]) -> Option<&'static Encoding> {
+    Encoding::for_bom(head).map(|(enc, _)| enc)
+}
+
+/// UTF-16 without BOM: text made of ASCII characters has a NUL every other byte.
+fn sniff_utf16(head: &[u8]) -> Option<&'static Encoding> {
+    let pairs = head.len() / 2;
+    if pairs < 16 {
+        return None;
+    }
+    let (mut even_nul, mut odd_nul) = (0, 0);
+    for pair in head.chunks_exact(2) {
+        if pair[0] == 0 && pair[1] != 0 {
+            even_nul += 1;
+        } else if pair[1] == 0 && pair[0] != 0 {
+            odd_nul += 1;
+        }
+    }
+    if odd_nul * 10 >= pairs * 9 {
+        Some(UTF_16LE)
+    } else if even_nul * 10 >= pairs * 9 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// `encoding: auto`: BOM, then BOM-less UTF-16, then chardet on the first block.
+fn sniff_encoding(head: &[u8]) -> Option<&'static Encoding> {
+    if let Some(enc) = sniff_bom(head).or_else(|| sniff_utf16(head)) {
+        return Some(enc);
+    }
+
+    let (charset, confidence, _) = chardet::detect(head);
+    let label = chardet::charset2encoding(&charset);
+    match Encoding::for_label(label.as_bytes()) {
+        Some(enc) if confidence > 0.0 => Some(enc),
+        // chardet reports pure ASCII as "ascii", which is valid UTF-8
+        _ if label.eq_ignore_ascii_case("ascii") => Some(UTF_8),
+        _ => None,
+    }
+}
+
+fn is_utf8_label(label: &str) -> bool {
+    label.eq_ignore_ascii_case("utf-8") || label.eq_ignore_ascii_case("utf8")
+}
+
+/// Wrap an input stream so the pipelines always read UTF-8, based on the `encoding`
+/// field of the pattern:
+///   - utf8 / utf-8 : read as-is (fast path)
+///   - auto         : BOM sniffing, then chardet on the first block
+///   - any label known to encoding_rs (utf-16le, windows-1252, latin1, shift_jis, ...)
+///   - unset or unknown : lossy UTF-8 (invalid sequences replaced)
+pub fn decoded_input(
+    input: Box<dyn Read + Send>,
+    encoding: Option<&str>,
+    path: &Path,
+    file_kind_label: &str,
+) -> Box<dyn Read + Send> {
+    let label = match encoding {
+        Some(l) => l.trim(),
+        None => {
+            // No encoding hint → assume non-UTF8 and be safe
+            warn!("No encoding metadata for {:?}, falling back to lossy reader.", path);
+            return Box::new(LossyUtf8Reader::new(input));
+        }
+    };
+
+    if is_utf8_label(label) {
+        info!("{} encoding '{}' detected for {:?}, using fast UTF-8 reader.", file_kind_label, label, path);
+        return input;
+    }
+
+    if label.eq_ignore_ascii_case("auto") {
+        let mut reader = BufReader::with_capacity(SNIFF_SIZE, input);
+        let detected = match reader.fill_buf() {
+            Ok(head) => sniff_encoding(head),
+            Err(e) => {
+                warn!("Cannot sniff encoding of {:?}: {}", path, e);
+                None
+            }
+        };
+
+        return match detected {
+            Some(enc) => {
+                info!("{} encoding '{}' detected for {:?} (auto).", file_kind_label, enc.name(), path);
+                Box::new(DecodingReader::new(reader, enc))
+            }
+            None => {
+                warn!("Could not detect the encoding of {:?}, falling back to lossy reader.", path);
+                Box::new(LossyUtf8Reader::new(reader))
+            }
+        };
+    }
+
+    match Encoding::for_label(label.as_bytes()) {
+        Some(enc) => {
+            info!("{} encoding '{}' for {:?}, decoding as {}.", file_kind_label, label, path, enc.name());
+            Box::new(DecodingReader::new(input, enc))
+        }
+        None => {
+            warn!("Unknown encoding '{}' for {:?}. Falling back to lossy reader.", label, path);
+            Box::new(LossyUtf8Reader::new(input))
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek};
+use std::path::Path;
+
+use evtx::{EvtxParser, ParserSettings};
+use log::debug;
+
+/// Magic of an EVTX file header.
+const EVTX_SIGNATURE: &[u8; 8] = b"ElfFile\x00";
+
+/// Seekable input of the EVTX parser.
+pub trait EvtxInput: Read + Seek + Send {}
+impl<T: Read + Seek + Send> EvtxInput for T {}
+
+pub type EvtxFileParser = EvtxParser<Box<dyn EvtxInput>>;
+
+/// True if the file on disk is a plain (uncompressed) EVTX file.
+fn is_plain_evtx(path: &Path) -> bool {
+    let mut magic = [0u8; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map(|_| &magic == EVTX_SIGNATURE)
+        .unwrap_or(false)
+}
+
+/// Open an EVTX parser.
+///
+/// EVTX parsing needs random access: plain files on disk are parsed in place, archive
+/// members and compressed files are read from `input` into memory.
+/// Records are rendered as JSON with the Event/System/EventData structure of evtx_dump
+/// (e.g. `Event.System.TimeCreated.#attributes.SystemTime`).
+pub fn open_evtx(path: &Path, in_archive: bool, mut input: Box<dyn Read + Send>) -> io::Result<EvtxFileParser> {
+    let source: Box<dyn EvtxInput> = if !in_archive && is_plain_evtx(path) {
+        Box::new(File::open(path)?)
+    } else {
+        let mut buf = Vec::new();
+        input.read_to_end(&mut buf)?;
+        debug!("Loaded {} bytes of EVTX data from {:?} in memory", buf.len(), path);
+        Box::new(Cursor::new(buf))
+    };
+
+    // Records are distributed over the worker pool by the pipeline (the crate is built
+    // without its own multithreading)
+    let settings = ParserSettings::new().separate_json_attributes(false);
+
+    EvtxParser::from_read_seek(source)
+        .map(|p| p.with_configuration(settings))
+        .map_err(|e| io::Error::other(e.to_string()))
+}
@@ -9,6 +9,11 @@ use serde::{Deserialize, Serialize};
 use serde_yaml::Value as YamlValue;
 use walkdir::WalkDir;
 
+use crate::utils::archive::{list_members, member_path, ArchiveKind, ArchiveMember};
+use crate::utils::compression::{inner_extension, inner_file_name};
+use crate::utils::lookup::LookupDefinition;
+use crate::utils::raw_parser::LineMerge;
+
 /// Base on the YAML fields:
 ///   - sourcetype: optional, default to source name
 ///   - timestamp_path: list of JSON paths
@@ -16,6 +21,9 @@ use walkdir::WalkDir;
 ///   - host_path: JSON path
 ///   - host_rex: regex to extract host from file path
 ///   - artifact: optional, default to source name
+///   - event_start_rex / event_indent: line merge rule of raw files
+///   - extract_rex: regex with named captures turned into fields of raw events
+///   - index: target index or template ("{case}_{artifact}"), default to --index
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileCriteria {
     pub name_rex: Option<String>,
@@ -29,6 +37,10 @@ pub struct FileCriteria {
     pub artifact: Option<String>,
     pub normalize: Option<Vec<String>>,
     pub encoding: Option<String>,
+    pub event_start_rex: Option<String>,
+    pub event_indent: Option<bool>,
+    pub extract_rex: Option<String>,
+    pub index: Option<String>,
 
     /// Precompiled regexes 
     #[serde(skip)]
@@ -42,12 +54,21 @@ pub struct FileCriteria {
     #[serde(skip)]
     #[serde(default)]
     pub host_re: Option<Regex>,
+
+    #[serde(skip)]
+    #[serde(default)]
+    pub event_start_re: Option<Regex>,
+
+    #[serde(skip)]
+    #[serde(default)]
+    pub extract_re: Option<Regex>,
 }
 
 /// Matched files metadata
 /// (file_path, sourcetype, host, timestamp_path, timestamp_format, host_path, source, artifact, normalize)
 #[derive(Debug, Clone)]
 pub struct FileTuple {
+    /// Path on disk, or "<archive>!/<inner path>" for archive members
     pub file_path: PathBuf,
     pub sourcetype: String,
     pub host: String,
@@ -58,6 +79,56 @@ pub struct FileTuple {
     pub artifact: String,
     pub normalize: Vec<String>,
     pub encoding: Option<String>,
+    /// Raw files only: how lines are merged into events
+    pub line_merge: Option<LineMerge>,
+    /// Raw files only: named captures extracted as event fields
+    pub extract_re: Option<Regex>,
+    /// Set when the file is a member of a zip/tar/7z archive
+    pub archive: Option<ArchiveMember>,
+    /// Index resolved from the pattern's index template (None = --index)
+    pub index: Option<String>,
+}
+
+/// Variables available in index templates.
+const INDEX_TEMPLATE_VARS: [&str; 5] = ["case", "source", "sourcetype", "artifact", "host"];
+
+/// Expand an index template: "{case}_{artifact}" → "case42_evtx".
+/// `vars` holds the values of INDEX_TEMPLATE_VARS, in the same order.
+fn render_index_template(template: &str, vars: [&str; 5]) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("unterminated '{{' in index template '{}'", template))?;
+        let name = &after[..end];
+        match INDEX_TEMPLATE_VARS.iter().position(|v| *v == name) {
+            Some(i) => out.push_str(vars[i]),
+            None => {
+                return Err(format!(
+                    "unknown variable '{{{}}}' in index template '{}' (expected one of {:?})",
+                    name, template, INDEX_TEMPLATE_VARS
+                ))
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(sanitize_index_name(&out))
+}
+
+/// Splunk index names are lowercase letters, digits, '_' and '-', and cannot start with '_' or '-'.
+fn sanitize_index_name(name: &str) -> String {
+    let cleaned: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    cleaned.trim_start_matches(['_', '-']).to_string()
 }
 
 /// Patterns are stored in an IndexMap to preserve YAML order.
@@ -72,6 +143,8 @@ pub struct FileMatcher {
 
     pub test_mode: bool,
     pub ext_filter: Option<Vec<String>>,
+    /// --index, the {case} variable of index templates
+    pub case: String,
 
     pub matched_files: Vec<PathBuf>,
     /// Unmatched files are fully stored only in test_mode, otherwise only the count is tracked to displays stats
@@ -84,10 +157,13 @@ pub struct FileMatcher {
 
     /// Final list of tuples used by Json2Splunk
     pub list_of_tuples: Vec<FileTuple>,
+
+    /// Lookup tables of the `lookups:` section, for the VRL lookup() function
+    pub lookups: IndexMap<String, LookupDefinition>,
 }
 
 impl FileMatcher {
-    pub fn new(pattern_file: PathBuf, test_mode: bool, ext_filter: Option<String>) -> Self {
+    pub fn new(pattern_file: PathBuf, test_mode: bool, ext_filter: Option<String>, case: &str) -> Self {
         if !pattern_file.exists() {
             error!(
                 "Pattern file not found: {:?}. Please check the --pattern-file path.",
@@ -140,6 +216,34 @@ impl FileMatcher {
             _ => raw_yaml.clone(),
         };
 
+        // Lookup tables are declared at the top level, next to the patterns
+        let lookups_key = YamlValue::String("lookups".to_string());
+        let mut patterns_node = patterns_node;
+        let lookups_node = match &raw_yaml {
+            YamlValue::Mapping(m) => m.get(&lookups_key).cloned(),
+            _ => None,
+        };
+        if let YamlValue::Mapping(ref mut m) = patterns_node {
+            m.remove(&lookups_key);
+        }
+        let mut lookups: IndexMap<String, LookupDefinition> = match lookups_node {
+            Some(node) => match serde_yaml::from_value(node) {
+                Ok(map) => map,
+                Err(e) => {
+                    error!("Invalid lookups section in pattern file {:?}: {}", pattern_file, e);
+                    std::process::exit(1);
+                }
+            },
+            None => IndexMap::new(),
+        };
+        // Table paths are relative to the pattern file
+        let base_dir = pattern_file.parent().map(Path::to_path_buf).unwrap_or_default();
+        for def in lookups.values_mut() {
+            if def.path.is_relative() {
+                def.path = base_dir.join(&def.path);
+            }
+        }
+
         // Deserialize the selected node into IndexMap<String, FileCriteria>
         let mut patterns_map: IndexMap<String, FileCriteria> =
             match serde_yaml::from_value(patterns_node.clone()) {
@@ -190,6 +294,60 @@ impl FileMatcher {
                     }
                 }
             }
+
+            if let Some(ref s) = crit.event_start_rex {
+                match Regex::new(s) {
+                    Ok(re) => crit.event_start_re = Some(re),
+                    Err(e) => {
+                        error!(
+                            "Invalid event_start_rex regex '{}' for pattern '{}': {}",
+                            s, name, e
+                        );
+                    }
+                }
+            }
+
+            if let Some(ref s) = crit.extract_rex {
+                match Regex::new(s) {
+                    Ok(re) => {
+                        if re.capture_names().flatten().next().is_none() {
+                            warn!(
+                                "extract_rex '{}' for pattern '{}' has no named capture group",
+                                s, name
+                            );
+                        }
+                        crit.extract_re = Some(re)
+                    }
+                    Err(e) => {
+                        error!(
+                            "Invalid extract_rex regex '{}' for pattern '{}': {}",
+                            s, name, e
+                        );
+                    }
+                }
+            }
+
+            // A wrong template would send data to the wrong index: refuse to start
+            if let Some(ref t) = crit.index {
+                match render_index_template(t, ["case", "source", "sourcetype", "artifact", "host"]) {
+                    Ok(idx) if idx.is_empty() => {
+                        error!("Index template '{}' for pattern '{}' gives an empty index name", t, name);
+                        std::process::exit(1);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Invalid index for pattern '{}': {}", name, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            if crit.event_start_rex.is_some() && crit.event_indent == Some(true) {
+                warn!(
+                    "Pattern '{}' sets both event_start_rex and event_indent, event_start_rex is used",
+                    name
+                );
+            }
         }
 
         // Transform ext arg from string like ".csv, .jsonl" to Vec<String>
@@ -210,22 +368,28 @@ impl FileMatcher {
             raw_patterns: raw_yaml,
             test_mode,
             ext_filter: ext_list,
+            case: case.to_string(),
             matched_files: vec![],
             unmatched_files: vec![],
             unmatched_count: 0,
             multi_match_count: 0,
             pattern_match_count,
             list_of_tuples: vec![],
+            lookups,
         }
     }
 
-    /// Determines if a file extension is allowed via --ext filter
+    /// Determines if a file extension is allowed via --ext filter.
+    /// For compressed files both the outer (".gz") and inner (".jsonl") extensions are checked.
     fn ext_allowed(&self, path: &Path) -> bool {
         if let Some(exts) = &self.ext_filter {
             if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                return exts.contains(&ext.to_lowercase());
+                if exts.contains(&ext.to_lowercase()) {
+                    return true;
+                }
             }
-            return false;
+            let inner = inner_extension(path);
+            return !inner.is_empty() && exts.contains(&inner);
         }
         true
     }
@@ -234,6 +398,7 @@ impl FileMatcher {
     ///
     /// AND logic:
     ///   - if name_rex is present, file *name* must match
+    ///     (for compressed files, the name with or without the compression extension)
     ///   - if path_suffix is present, parent dir path must end with it
     ///   - if path_rex is present, parent dir path must match it
     ///
@@ -248,6 +413,9 @@ impl FileMatcher {
             .and_then(|s| s.to_str())
             .unwrap_or("");
 
+        // File name without its compression extension ("a.jsonl.gz" → "a.jsonl")
+        let inner_name = inner_file_name(path);
+
         // Parent directory path as a string, for path_suffix / path_rex
         let dir_path: Cow<'_, str> = path
             .parent()
@@ -259,7 +427,7 @@ impl FileMatcher {
 
             // 1) If name_re is defined, the *file name* must match it
             if let Some(re) = &criteria.name_re {
-                if !re.is_match(file_name) {
+                if !re.is_match(file_name) && !re.is_match(&inner_name) {
                     ok = false;
                 }
             }
@@ -286,7 +454,9 @@ impl FileMatcher {
         matches
     }
 
-    /// Scans the directory recursively for files matching ext + patterns
+    /// Scans the directory recursively for files matching ext + patterns.
+    /// Archives (zip, tar, 7z) found while walking, or given as input, are
+    /// expanded: their members are matched by "<archive>!/<inner path>".
     ///
     /// Builds:
     ///   - matched_files / unmatched_count / multi_match_count
@@ -296,117 +466,185 @@ impl FileMatcher {
         for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
             let path = entry.path();
             if path.is_file() {
-                if !self.ext_allowed(path) {
-                    continue;
+                if let Some(kind) = ArchiveKind::from_path(path) {
+                    self.add_archive(path, kind);
+                } else {
+                    self.add_file(path, None);
                 }
+            }
+        }
+    }
 
-                let matches = self.match_file(path);
+    /// Match every member of an archive against the patterns.
+    fn add_archive(&mut self, archive: &Path, kind: ArchiveKind) {
+        let members = match list_members(archive, kind) {
+            Ok(m) => m,
+            Err(e) => {
+                error!("Cannot list archive {:?}: {}", archive, e);
+                return;
+            }
+        };
 
-                if matches.is_empty() {
-                    self.unmatched_count += 1;
-                    if self.test_mode {
-                        self.unmatched_files.push(path.to_path_buf());
-                    }
-                    continue;
-                }
+        info!("Archive {:?}: {} members", archive, members.len());
 
-                if matches.len() > 1 {
-                    self.multi_match_count += 1;
-                    warn!("MULTI-MATCH: {:?} => {:?}", path, matches);
-                }
+        for name in members {
+            let path = member_path(archive, &name);
+            let member = ArchiveMember {
+                archive: archive.to_path_buf(),
+                kind,
+                name,
+            };
+            self.add_file(&path, Some(member));
+        }
+    }
 
-                // Primary pattern = first match in YAML order
-                let primary_source = matches[0].clone();
-                let criteria = match self.patterns.get(&primary_source) {
-                    Some(c) => c,
-                    None => {
-                        // Should not happen, but be defensive.
-                        warn!(
-                            "Pattern '{}' not found in patterns map for file {:?}",
-                            primary_source, path
-                        );
-                        continue;
-                    }
-                };
-
-                // sourcetype: default to source name
-                let sourcetype = criteria
-                    .sourcetype
-                    .clone()
-                    .unwrap_or_else(|| primary_source.clone());
-
-                // timestamp_path: default empty vec
-                let timestamp_path = criteria
-                    .timestamp_path
-                    .clone()
-                    .unwrap_or_else(|| Vec::new());
-
-                // timestamp_format: default empty string
-                let timestamp_format = criteria
-                    .timestamp_format
-                    .clone()
-                    .unwrap_or_else(|| String::new());
-
-                // artifact: default to source name
-                let artifact = criteria
-                    .artifact
-                    .clone()
-                    .unwrap_or_else(|| primary_source.clone());
-
-                let normalize = criteria
-                    .normalize
-                    .clone()
-                    .unwrap_or_else(|| Vec::new());
-
-                let encoding = criteria.encoding.clone();
-
-                // Host logic (per Python):
-                //   - start with "Unknown"
-                //   - if host_rex is set, extract from file path
-                //   - if host_path is set, we only set host_path (host will be extracted from JSON later)
-                let mut host = "Unknown".to_string();
-                let host_path = criteria.host_path.clone();
-                if let Some(re) = &criteria.host_re {
-                    if let Some(caps) = re.captures(&path.to_string_lossy()) {
-                        if let Some(m) = caps.get(1) {
-                            host = m.as_str().to_string();
-                        }
-                    }
+    /// Match a file (or archive member) and record its tuple.
+    fn add_file(&mut self, path: &Path, archive: Option<ArchiveMember>) {
+        if !self.ext_allowed(path) {
+            return;
+        }
+
+        let matches = self.match_file(path);
+
+        if matches.is_empty() {
+            self.unmatched_count += 1;
+            if self.test_mode {
+                self.unmatched_files.push(path.to_path_buf());
+            }
+            return;
+        }
+
+        if matches.len() > 1 {
+            self.multi_match_count += 1;
+            warn!("MULTI-MATCH: {:?} => {:?}", path, matches);
+        }
+
+        // Primary pattern = first match in YAML order
+        let primary_source = matches[0].clone();
+        let criteria = match self.patterns.get(&primary_source) {
+            Some(c) => c,
+            None => {
+                // Should not happen, but be defensive.
+                warn!(
+                    "Pattern '{}' not found in patterns map for file {:?}",
+                    primary_source, path
+                );
+                return;
+            }
+        };
+
+        // sourcetype: default to source name
+        let sourcetype = criteria
+            .sourcetype
+            .clone()
+            .unwrap_or_else(|| primary_source.clone());
+
+        // timestamp_path: default empty vec
+        let timestamp_path = criteria
+            .timestamp_path
+            .clone()
+            .unwrap_or_else(|| Vec::new());
+
+        // timestamp_format: default empty string
+        let timestamp_format = criteria
+            .timestamp_format
+            .clone()
+            .unwrap_or_else(|| String::new());
+
+        // artifact: default to source name
+        let artifact = criteria
+            .artifact
+            .clone()
+            .unwrap_or_else(|| primary_source.clone());
+
+        let normalize = criteria
+            .normalize
+            .clone()
+            .unwrap_or_else(|| Vec::new());
+
+        let encoding = criteria.encoding.clone();
+
+        // Line merge rule for raw files: a start regex wins over indentation
+        let line_merge = match (&criteria.event_start_re, criteria.event_indent) {
+            (Some(re), _) => Some(LineMerge::StartRegex(re.clone())),
+            (None, Some(true)) => Some(LineMerge::Indent),
+            _ => None,
+        };
+        let extract_re = criteria.extract_re.clone();
+
+        // Host logic (per Python):
+        //   - start with "Unknown"
+        //   - if host_rex is set, extract from file path
+        //   - if host_path is set, we only set host_path (host will be extracted from JSON later)
+        let mut host = "Unknown".to_string();
+        let host_path = criteria.host_path.clone();
+        if let Some(re) = &criteria.host_re {
+            if let Some(caps) = re.captures(&path.to_string_lossy()) {
+                if let Some(m) = caps.get(1) {
+                    host = m.as_str().to_string();
                 }
+            }
+        }
 
-                // Normalize host: lower, split by . and take the first
-                let host_norm = {
-                    let lower = host.to_lowercase();
-                    match lower.split('.').next() {
-                        Some(first) if !first.is_empty() => first.to_string(),
-                        _ => lower,
-                    }
-                };
-
-                let tuple = FileTuple {
-                    file_path: path.to_path_buf(),
-                    sourcetype,
-                    host: host_norm,
-                    timestamp_path,
-                    timestamp_format,
-                    host_path,
-                    source: primary_source.clone(),
-                    artifact,
-                    normalize,
-                    encoding,
-                };
-
-                self.matched_files.push(path.to_path_buf());
-                self.list_of_tuples.push(tuple);
-
-                // Update primary pattern match count in YAML order
-                if let Some(counter) = self.pattern_match_count.get_mut(&primary_source) {
-                    *counter += 1;
+        // Normalize host: lower, split by . and take the first
+        let host_norm = {
+            let lower = host.to_lowercase();
+            match lower.split('.').next() {
+                Some(first) if !first.is_empty() => first.to_string(),
+                _ => lower,
+            }
+        };
+
+        // Index routing: {host} is the host found in the file path (host_rex)
+        let index = criteria.index.as_deref().and_then(|t| {
+            match render_index_template(t, [&self.case, &primary_source, &sourcetype, &artifact, &host_norm]) {
+                Ok(idx) if !idx.is_empty() => Some(idx),
+                _ => {
+                    warn!("Index template '{}' gives no valid index for {:?}, using --index", t, path);
+                    None
                 }
             }
+        });
+
+        let tuple = FileTuple {
+            file_path: path.to_path_buf(),
+            sourcetype,
+            host: host_norm,
+            timestamp_path,
+            timestamp_format,
+            host_path,
+            source: primary_source.clone(),
+            artifact,
+            normalize,
+            encoding,
+            line_merge,
+            extract_re,
+            archive,
+            index,
+        };
+
+        self.matched_files.push(path.to_path_buf());
+        self.list_of_tuples.push(tuple);
+
+        // Update primary pattern match count in YAML order
+        if let Some(counter) = self.pattern_match_count.get_mut(&primary_source) {
+            *counter += 1;
         }
     }
 
+    /// Indexes the matched files are routed to by index templates, --index excluded.
+    pub fn routed_indexes(&self) -> Vec<String> {
+        let mut indexes: Vec<String> = self
+            .list_of_tuples
+            .iter()
+            .filter_map(|t| t.index.clone())
+            .filter(|idx| *idx != self.case)
+            .collect();
+        indexes.sort();
+        indexes.dedup();
+        indexes
+    }
+
     pub fn print_statistics(&self) {
         info!("========== FILE MATCHER REPORT ==========");
         info!("Matched files: {}", self.matched_files.len());
@@ -422,6 +660,18 @@ impl FileMatcher {
             );
         }
 
+        // Files per target index, when index templates route some of them
+        if self.list_of_tuples.iter().any(|t| t.index.is_some()) {
+            let mut per_index: IndexMap<&str, usize> = IndexMap::new();
+            for t in &self.list_of_tuples {
+                *per_index.entry(t.index.as_deref().unwrap_or(&self.case)).or_insert(0) += 1;
+            }
+            per_index.sort_keys();
+            for (index, count) in per_index {
+                info!("Number of files routed to index '{}': {}", index, count);
+            }
+        }
+
         if self.test_mode {
             info!("Test mode: writing test_files_to_index.json");
 
@@ -438,6 +688,7 @@ impl FileMatcher {
                     "timestamp_format": t.timestamp_format,
                     "host_path": t.host_path,
                     "artifact": t.artifact,
+                    "index": t.index.as_deref().unwrap_or(&self.case),
                 }));
             }
 
@@ -449,3 +700,57 @@ impl FileMatcher {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARS: [&str; 5] = ["Case42", "evtx", "winevtx", "EVTX", "WS-01"];
+
+    #[test]
+    fn render_known_variables() {
+        assert_eq!(render_index_template("{case}_{artifact}", VARS).unwrap(), "case42_evtx");
+        assert_eq!(
+            render_index_template("{case}-{source}-{sourcetype}-{host}", VARS).unwrap(),
+            "case42-evtx-winevtx-ws-01"
+        );
+        assert_eq!(render_index_template("static_index", VARS).unwrap(), "static_index");
+        assert_eq!(render_index_template("{host}{host}", VARS).unwrap(), "ws-01ws-01");
+    }
+
+    #[test]
+    fn render_unknown_variable() {
+        let err = render_index_template("{case}_{user}", VARS).unwrap_err();
+        assert!(err.contains("unknown variable '{user}'"), "{}", err);
+        assert!(render_index_template("{}", VARS).unwrap_err().contains("unknown variable '{}'"));
+        assert!(render_index_template("{Case}", VARS).is_err());
+    }
+
+    #[test]
+    fn render_unterminated() {
+        let err = render_index_template("{case", VARS).unwrap_err();
+        assert!(err.contains("unterminated"), "{}", err);
+        assert!(render_index_template("{case}_{", VARS).is_err());
+        // A lone closing brace is not a variable
+        assert_eq!(render_index_template("a}b", VARS).unwrap(), "a_b");
+    }
+
+    #[test]
+    fn render_sanitizes_values() {
+        let vars = ["My Case", "src", "st", "art", "host.domain.local"];
+        assert_eq!(render_index_template("{case}_{host}", vars).unwrap(), "my_case_host_domain_local");
+        // The values would make the name start with '_' or '-'
+        let vars = ["", "src", "st", "-art", "host"];
+        assert_eq!(render_index_template("{case}_{artifact}", vars).unwrap(), "art");
+    }
+
+    #[test]
+    fn sanitize() {
+        assert_eq!(sanitize_index_name("Case_42-B"), "case_42-b");
+        assert_eq!(sanitize_index_name("a b.c/d:é"), "a_b_c_d__");
+        assert_eq!(sanitize_index_name("__-_idx"), "idx");
+        assert_eq!(sanitize_index_name("idx_-"), "idx_-");
+        assert_eq!(sanitize_index_name(""), "");
+        assert_eq!(sanitize_index_name("_-_"), "");
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use indexmap::IndexMap;
+use log::{info, warn};
+use serde::Deserialize;
+use vrl::value::{ObjectMap, Value as VrlValue};
+
+/// Tables loaded at startup, shared by every worker and VRL program.
+static LOOKUP_TABLES: OnceLock<HashMap<String, LookupTable>> = OnceLock::new();
+
+/// How a condition value is compared with a column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    #[default]
+    Exact,
+    CaseInsensitive,
+    /// The column holds networks ("10.0.0.0/8", or a single address), the value an IP
+    Cidr,
+}
+
+impl MatchMode {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "exact" => Some(MatchMode::Exact),
+            "case_insensitive" => Some(MatchMode::CaseInsensitive),
+            "cidr" => Some(MatchMode::Cidr),
+            _ => None,
+        }
+    }
+}
+
+/// `match:` of a table: one mode for every column, or a mode per column (others are exact).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum MatchSpec {
+    All(MatchMode),
+    Columns(HashMap<String, MatchMode>),
+}
+
+impl Default for MatchSpec {
+    fn default() -> Self {
+        MatchSpec::All(MatchMode::Exact)
+    }
+}
+
+impl MatchSpec {
+    fn mode(&self, column: &str) -> MatchMode {
+        match self {
+            MatchSpec::All(mode) => *mode,
+            MatchSpec::Columns(modes) => modes.get(column).copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// A table of the `lookups:` section of the patterns file, or of --lookup.
+///
+///   lookups:
+///     assets:
+///       path: lookups/assets.csv       # CSV with a header row, or JSONL (.jsonl / .json)
+///       match: case_insensitive        # exact (default), case_insensitive or cidr
+///     networks:
+///       path: lookups/networks.csv
+///       match: { subnet: cidr }        # per column, the others are exact
+#[derive(Debug, Clone, Deserialize)]
+pub struct LookupDefinition {
+    pub path: PathBuf,
+    #[serde(default, rename = "match")]
+    pub match_spec: MatchSpec,
+}
+
+impl LookupDefinition {
+    /// --lookup NAME=PATH[:MODE], e.g. "bad_hashes=iocs.csv" or "assets=assets.csv:case_insensitive".
+    pub fn from_cli(arg: &str) -> Result<(String, LookupDefinition), String> {
+        let (name, rest) = arg
+            .split_once('=')
+            .filter(|(name, path)| !name.is_empty() && !path.is_empty())
+            .ok_or_else(|| format!("invalid --lookup '{}': expected NAME=PATH[:MODE]", arg))?;
+
+        // A trailing ":<mode>" is a match mode; anything else belongs to the path (C:\...)
+        let (path, mode) = match rest.rsplit_once(':') {
+            Some((path, mode)) if MatchMode::parse(mode).is_some() => (path, MatchMode::parse(mode).unwrap()),
+            _ => (rest, MatchMode::Exact),
+        };
+
+        Ok((
+            name.to_string(),
+            LookupDefinition {
+                path: PathBuf::from(path),
+                match_spec: MatchSpec::All(mode),
+            },
+        ))
+    }
+}
+
+/// A network of a CIDR column.
+#[derive(Debug, Clone, Copy)]
+struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+            None => (s.parse::<IpAddr>().ok()?, None),
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(Cidr { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let (net, ip, bits) = match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => (u32::from(net) as u128, u32::from(ip) as u128, 32),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => (u128::from(net), u128::from(ip), 128),
+            _ => return false,
+        };
+        let shift = bits - self.prefix as u32;
+        // A /0 network: shifting by the full width would overflow
+        shift >= bits || (net >> shift) == (ip >> shift)
+    }
+}
+
+/// Comparable text of a value: strings as-is, numbers and booleans in their JSON form.
+fn value_text(value: &VrlValue) -> Option<String> {
+    match value {
+        VrlValue::Bytes(b) => Some(String::from_utf8_lossy(b).into_owned()),
+        VrlValue::Integer(_) | VrlValue::Float(_) | VrlValue::Boolean(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// A loaded table: its rows, an index per exact / case-insensitive column and the parsed
+/// networks of the CIDR columns.
+#[derive(Debug)]
+pub struct LookupTable {
+    rows: Vec<ObjectMap>,
+    match_spec: MatchSpec,
+    /// column → normalized value → rows, in file order
+    indexes: HashMap<String, HashMap<String, Vec<usize>>>,
+    /// column → network of each row
+    networks: HashMap<String, Vec<Option<Cidr>>>,
+}
+
+impl LookupTable {
+    pub fn load(def: &LookupDefinition) -> Result<Self, String> {
+        let rows = match def.path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()) {
+            Some(ext) if ext == "jsonl" || ext == "json" => read_jsonl(&def.path)?,
+            _ => read_csv(&def.path)?,
+        };
+
+        let mut indexes: HashMap<String, HashMap<String, Vec<usize>>> = HashMap::new();
+        let mut networks: HashMap<String, Vec<Option<Cidr>>> = HashMap::new();
+
+        for (i, row) in rows.iter().enumerate() {
+            for (column, value) in row.iter() {
+                let column = column.as_str();
+                match def.match_spec.mode(column) {
+                    MatchMode::Cidr => {
+                        let nets = networks.entry(column.to_string()).or_insert_with(|| vec![None; rows.len()]);
+                        nets[i] = value_text(value).and_then(|s| Cidr::parse(&s));
+                    }
+                    mode => {
+                        if let Some(text) = value_text(value) {
+                            let key = if mode == MatchMode::CaseInsensitive { text.to_lowercase() } else { text };
+                            indexes.entry(column.to_string()).or_default().entry(key).or_default().push(i);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(LookupTable {
+            rows,
+            match_spec: def.match_spec.clone(),
+            indexes,
+            networks,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// First row matching every condition (column → value). With CIDR conditions, the row
+    /// with the most specific network wins.
+    pub fn find(&self, conditions: &ObjectMap) -> Option<&ObjectMap> {
+        if conditions.is_empty() {
+            return None;
+        }
+
+        // Conditions on exact / case-insensitive columns, normalized like the index keys
+        let mut keyed = Vec::new();
+        let mut cidr = Vec::new();
+        for (column, value) in conditions.iter() {
+            let column = column.as_str();
+            match self.match_spec.mode(column) {
+                MatchMode::Cidr => {
+                    let ip = value_text(value)?.trim().parse::<IpAddr>().ok()?;
+                    cidr.push((self.networks.get(column)?, ip));
+                }
+                mode => {
+                    let text = value_text(value)?;
+                    let key = if mode == MatchMode::CaseInsensitive { text.to_lowercase() } else { text };
+                    keyed.push((self.indexes.get(column)?, key));
+                }
+            }
+        }
+
+        // Candidates from the first indexed column, or every row for CIDR-only lookups
+        let candidates: Box<dyn Iterator<Item = usize>> = match keyed.first() {
+            Some((index, key)) => Box::new(index.get(key)?.iter().copied()),
+            None => Box::new(0..self.rows.len()),
+        };
+
+        let mut best: Option<(usize, u32)> = None;
+        for i in candidates {
+            // Index entries are in row order
+            let other_keys_match = keyed
+                .iter()
+                .skip(1)
+                .all(|(index, key)| index.get(key).is_some_and(|rows| rows.binary_search(&i).is_ok()));
+            if !other_keys_match {
+                continue;
+            }
+
+            let mut specificity = 0;
+            let mut contained = true;
+            for (nets, ip) in &cidr {
+                match nets[i] {
+                    Some(net) if net.contains(*ip) => specificity += net.prefix as u32,
+                    _ => {
+                        contained = false;
+                        break;
+                    }
+                }
+            }
+            if !contained {
+                continue;
+            }
+
+            if cidr.is_empty() {
+                return Some(&self.rows[i]);
+            }
+            if best.is_none_or(|(_, s)| specificity > s) {
+                best = Some((i, specificity));
+            }
+        }
+
+        best.map(|(i, _)| &self.rows[i])
+    }
+}
+
+/// CSV with a header row: every cell is a string.
+fn read_csv(path: &Path) -> Result<Vec<ObjectMap>, String> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+
+    let headers: Vec<String> = rdr
+        .headers()
+        .map_err(|e| format!("cannot read the header of {}: {}", path.display(), e))?
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').trim().to_string())
+        .collect();
+
+    let mut rows = Vec::new();
+    for (n, record) in rdr.records().enumerate() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Lookup {}: row {} could not be parsed: {}. Skipping.", path.display(), n + 1, e);
+                continue;
+            }
+        };
+        let row: ObjectMap = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(h, v)| (h.as_str().into(), VrlValue::from(v)))
+            .collect();
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// One JSON object per line.
+fn read_jsonl(path: &Path) -> Result<Vec<ObjectMap>, String> {
+    let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+
+    let mut rows = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<VrlValue>(&line) {
+            Ok(VrlValue::Object(row)) => rows.push(row),
+            Ok(_) => warn!("Lookup {}: line {} is not a JSON object. Skipping.", path.display(), n + 1),
+            Err(e) => warn!("Lookup {}: line {} is not valid JSON: {}. Skipping.", path.display(), n + 1, e),
+        }
+    }
+    Ok(rows)
+}
+
+/// Load the tables once for the whole run. Returns false if a table cannot be loaded.
+pub fn init(definitions: &IndexMap<String, LookupDefinition>) -> bool {
+    let mut tables = HashMap::new();
+
+    for (name, def) in definitions {
+        match LookupTable::load(def) {
+            Ok(table) => {
+                info!("Lookup table '{}': {} rows from {}", name, table.len(), def.path.display());
+                tables.insert(name.clone(), table);
+            }
+            Err(e) => {
+                log::error!("Cannot load lookup table '{}': {}", name, e);
+                return false;
+            }
+        }
+    }
+
+    if LOOKUP_TABLES.set(tables).is_err() {
+        warn!("Lookup tables are already loaded");
+    }
+    true
+}
+
+/// A loaded table, by name.
+pub fn table(name: &str) -> Option<&'static LookupTable> {
+    LOOKUP_TABLES.get()?.get(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> Cidr {
+        Cidr::parse(s).unwrap_or_else(|| panic!("invalid network {}", s))
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    /// Load a CSV table written to a temporary file.
+    fn load_csv(name: &str, csv: &str, match_spec: MatchSpec) -> LookupTable {
+        let path = std::env::temp_dir().join(format!("json2splunk-lookup-{}-{}.csv", std::process::id(), name));
+        std::fs::write(&path, csv).unwrap();
+        let table = LookupTable::load(&LookupDefinition { path: path.clone(), match_spec });
+        let _ = std::fs::remove_file(&path);
+        table.unwrap()
+    }
+
+    fn conditions(pairs: &[(&str, &str)]) -> ObjectMap {
+        pairs.iter().map(|(k, v)| ((*k).into(), VrlValue::from(*v))).collect()
+    }
+
+    fn column(row: Option<&ObjectMap>, name: &str) -> Option<String> {
+        Some(row?.get(name)?.as_str()?.into_owned())
+    }
+
+    #[test]
+    fn cidr_parse() {
+        assert_eq!(cidr("10.0.0.0/8").prefix, 8);
+        assert_eq!(cidr(" 10.1.2.3 ").prefix, 32);
+        assert_eq!(cidr("fe80::/10").prefix, 10);
+        assert_eq!(cidr("::1").prefix, 128);
+        assert!(Cidr::parse("10.0.0.0/33").is_none());
+        assert!(Cidr::parse("fe80::/129").is_none());
+        assert!(Cidr::parse("10.0.0.0/").is_none());
+        assert!(Cidr::parse("10.0.0/8").is_none());
+        assert!(Cidr::parse("host-01").is_none());
+    }
+
+    #[test]
+    fn cidr_contains() {
+        let net = cidr("10.1.0.0/16");
+        assert!(net.contains(ip("10.1.0.0")));
+        assert!(net.contains(ip("10.1.255.255")));
+        assert!(!net.contains(ip("10.2.0.0")));
+        assert!(!net.contains(ip("10.0.255.255")));
+
+        // A bare address is a /32 (/128)
+        assert!(cidr("192.168.1.10").contains(ip("192.168.1.10")));
+        assert!(!cidr("192.168.1.10").contains(ip("192.168.1.11")));
+        assert!(cidr("2001:db8::1").contains(ip("2001:db8::1")));
+        assert!(!cidr("2001:db8::1").contains(ip("2001:db8::2")));
+
+        // /0 matches every address of its family
+        assert!(cidr("0.0.0.0/0").contains(ip("255.255.255.255")));
+        assert!(cidr("::/0").contains(ip("2001:db8::1")));
+
+        // IPv4 and IPv6 never match each other, even IPv4-mapped addresses
+        assert!(!cidr("0.0.0.0/0").contains(ip("::1")));
+        assert!(!cidr("::/0").contains(ip("10.0.0.1")));
+        assert!(!cidr("10.0.0.0/8").contains(ip("::ffff:10.0.0.1")));
+
+        assert!(cidr("2001:db8::/32").contains(ip("2001:db8:ffff::1")));
+        assert!(!cidr("2001:db8::/32").contains(ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn most_specific_network_wins() {
+        let table = load_csv(
+            "networks",
+            "subnet,zone\n0.0.0.0/0,internet\n10.0.0.0/8,corp\n10.1.2.0/24,dmz\n10.1.0.0/16,site\n::/0,internet6\n",
+            MatchSpec::All(MatchMode::Cidr),
+        );
+        let zone = |addr: &str| column(table.find(&conditions(&[("subnet", addr)])), "zone");
+
+        assert_eq!(zone("10.1.2.3").as_deref(), Some("dmz"));
+        assert_eq!(zone("10.1.3.3").as_deref(), Some("site"));
+        assert_eq!(zone("10.9.0.1").as_deref(), Some("corp"));
+        assert_eq!(zone("8.8.8.8").as_deref(), Some("internet"));
+        assert_eq!(zone("2001:db8::1").as_deref(), Some("internet6"));
+        assert_eq!(zone("not an ip"), None);
+    }
+
+    #[test]
+    fn cidr_with_exact_columns() {
+        let mut modes = HashMap::new();
+        modes.insert("subnet".to_string(), MatchMode::Cidr);
+        let table = load_csv(
+            "sites",
+            "site,subnet,owner\nparis,10.0.0.0/8,it\nparis,10.1.0.0/16,soc\nlyon,10.1.0.0/24,ops\n",
+            MatchSpec::Columns(modes),
+        );
+        let owner = |site: &str, addr: &str| {
+            column(table.find(&conditions(&[("site", site), ("subnet", addr)])), "owner")
+        };
+
+        assert_eq!(owner("paris", "10.1.0.1").as_deref(), Some("soc"));
+        assert_eq!(owner("paris", "10.2.0.1").as_deref(), Some("it"));
+        assert_eq!(owner("lyon", "10.1.0.1").as_deref(), Some("ops"));
+        assert_eq!(owner("lyon", "10.2.0.1"), None);
+        // "site" is exact
+        assert_eq!(owner("Paris", "10.1.0.1"), None);
+    }
+
+    #[test]
+    fn exact_and_case_insensitive() {
+        let csv = "host,owner\nWS-01,alice\nws-01,bob\nWS-02,carol\n";
+        let exact = load_csv("exact", csv, MatchSpec::default());
+        assert_eq!(column(exact.find(&conditions(&[("host", "ws-01")])), "owner").as_deref(), Some("bob"));
+        assert_eq!(column(exact.find(&conditions(&[("host", "Ws-02")])), "owner").as_deref(), None);
+
+        let insensitive = load_csv("insensitive", csv, MatchSpec::All(MatchMode::CaseInsensitive));
+        // The first row in file order
+        assert_eq!(column(insensitive.find(&conditions(&[("host", "ws-01")])), "owner").as_deref(), Some("alice"));
+        assert_eq!(column(insensitive.find(&conditions(&[("host", "Ws-02")])), "owner").as_deref(), Some("carol"));
+
+        assert!(exact.find(&ObjectMap::new()).is_none());
+        assert!(exact.find(&conditions(&[("unknown", "x")])).is_none());
+    }
+
+    #[test]
+    fn definition_from_cli() {
+        let (name, def) = LookupDefinition::from_cli("iocs=lookups/iocs.csv").unwrap();
+        assert_eq!(name, "iocs");
+        assert_eq!(def.path, PathBuf::from("lookups/iocs.csv"));
+        assert!(matches!(def.match_spec, MatchSpec::All(MatchMode::Exact)));
+
+        let (_, def) = LookupDefinition::from_cli("assets=assets.csv:case_insensitive").unwrap();
+        assert_eq!(def.path, PathBuf::from("assets.csv"));
+        assert!(matches!(def.match_spec, MatchSpec::All(MatchMode::CaseInsensitive)));
+
+        // Windows paths: the drive colon belongs to the path
+        let (name, def) = LookupDefinition::from_cli(r"x=C:\t\a.csv:cidr").unwrap();
+        assert_eq!(name, "x");
+        assert_eq!(def.path, PathBuf::from(r"C:\t\a.csv"));
+        assert!(matches!(def.match_spec, MatchSpec::All(MatchMode::Cidr)));
+
+        let (_, def) = LookupDefinition::from_cli(r"x=C:\t\a.csv").unwrap();
+        assert_eq!(def.path, PathBuf::from(r"C:\t\a.csv"));
+        assert!(matches!(def.match_spec, MatchSpec::All(MatchMode::Exact)));
+
+        // An unknown mode is part of the path
+        let (_, def) = LookupDefinition::from_cli("x=a.csv:fuzzy").unwrap();
+        assert_eq!(def.path, PathBuf::from("a.csv:fuzzy"));
+
+        for invalid in ["assets.csv", "=assets.csv", "assets=", ""] {
+            let err = LookupDefinition::from_cli(invalid).unwrap_err();
+            assert!(err.contains("expected NAME=PATH[:MODE]"), "{}", err);
+        }
+    }
+}
@@ -1,3 +1,13 @@
+pub mod archive;
+pub mod checkpoint;
+pub mod compression;
+pub mod decoding;
+pub mod evtx_reader;
 pub mod file_matcher;
+pub mod lookup;
+pub mod raw_parser;
+pub mod secrets;
 pub mod utils;
-pub mod vrl;
\ No newline at end of file
+pub mod vrl;
+pub mod vrl_functions;
+pub mod vrl_test;
\ No newline at end of file
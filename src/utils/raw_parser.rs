@@ -0,0 +1,169 @@
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// Upper bound on the lines merged into one raw event, so a start regex that never
+/// matches cannot buffer a whole file in memory.
+const MAX_EVENT_LINES: usize = 10_000;
+
+/// How consecutive lines of a raw file are merged into one event.
+#[derive(Debug, Clone)]
+pub enum LineMerge {
+    /// A line matching the regex starts a new event, other lines are appended to the current one
+    StartRegex(Regex),
+    /// A line starting with a space or a tab continues the previous event
+    Indent,
+}
+
+impl LineMerge {
+    /// True if `line` belongs to the event started by a previous line.
+    fn continues(&self, line: &str) -> bool {
+        match self {
+            LineMerge::StartRegex(re) => !re.is_match(line),
+            LineMerge::Indent => line.starts_with([' ', '\t']),
+        }
+    }
+}
+
+/// Raw event made of one or more consecutive lines.
+pub struct RawEvent {
+    pub first_line: usize,
+    pub last_line: usize,
+    pub text: String,
+}
+
+/// Group the (line number, content) pairs of a raw file into events.
+///
+/// Lines are joined with '\n'. Continuation lines found before the first start line
+/// form their own event, so nothing is dropped.
+pub fn merge_lines<I>(lines: I, rule: LineMerge) -> impl Iterator<Item = RawEvent>
+where
+    I: Iterator<Item = (usize, String)>,
+{
+    let mut lines = lines.peekable();
+
+    std::iter::from_fn(move || {
+        let (first_line, mut text) = lines.next()?;
+        let mut last_line = first_line;
+        let mut count = 1;
+
+        while count < MAX_EVENT_LINES {
+            match lines.next_if(|(_, line)| rule.continues(line)) {
+                Some((lineno, line)) => {
+                    text.push('\n');
+                    text.push_str(&line);
+                    last_line = lineno;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+
+        Some(RawEvent { first_line, last_line, text })
+    })
+}
+
+/// Build the record of a raw event: `{"message": text}`, plus one field per named capture
+/// of `extract_re` when it matches. A capture named `message` replaces the full text.
+pub fn raw_record(text: String, extract_re: Option<&Regex>) -> Value {
+    let mut record = Map::new();
+
+    if let Some(re) = extract_re
+        && let Some(caps) = re.captures(&text)
+    {
+        for name in re.capture_names().flatten() {
+            if let Some(m) = caps.name(name) {
+                record.insert(name.to_string(), Value::String(m.as_str().to_string()));
+            }
+        }
+    }
+
+    record.entry("message").or_insert(Value::String(text));
+    Value::Object(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (first line, last line, text) of the events of `lines`, numbered from 1.
+    fn events(lines: &[&str], rule: LineMerge) -> Vec<(usize, usize, String)> {
+        let numbered: Vec<(usize, String)> = lines.iter().enumerate().map(|(i, l)| (i + 1, l.to_string())).collect();
+        merge_lines(numbered.into_iter(), rule)
+            .map(|e| (e.first_line, e.last_line, e.text))
+            .collect()
+    }
+
+    #[test]
+    fn merge_lines_start_regex() {
+        let rule = LineMerge::StartRegex(Regex::new(r"^\d{4}-").unwrap());
+        let lines = ["  orphan", "2024-01-01 a", "  at x", "  at y", "2024-01-02 b", "2024-01-03 c", "tail"];
+        assert_eq!(
+            events(&lines, rule),
+            vec![
+                (1, 1, "  orphan".to_string()),
+                (2, 4, "2024-01-01 a\n  at x\n  at y".to_string()),
+                (5, 5, "2024-01-02 b".to_string()),
+                (6, 7, "2024-01-03 c\ntail".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_lines_indent() {
+        let lines = ["a", " b", "\tc", "", "d", "e"];
+        assert_eq!(
+            events(&lines, LineMerge::Indent),
+            vec![
+                (1, 3, "a\n b\n\tc".to_string()),
+                (4, 4, String::new()),
+                (5, 5, "d".to_string()),
+                (6, 6, "e".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_lines_empty_input() {
+        assert!(events(&[], LineMerge::Indent).is_empty());
+    }
+
+    #[test]
+    fn merge_lines_caps_event_size() {
+        let mut lines = vec!["start"];
+        lines.extend(std::iter::repeat_n(" more", MAX_EVENT_LINES));
+        let merged = events(&lines, LineMerge::Indent);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!((merged[0].0, merged[0].1), (1, MAX_EVENT_LINES));
+        assert_eq!(merged[0].2.lines().count(), MAX_EVENT_LINES);
+        // The line over the limit starts the next event
+        assert_eq!(merged[1], (MAX_EVENT_LINES + 1, MAX_EVENT_LINES + 1, " more".to_string()));
+    }
+
+    #[test]
+    fn raw_record_without_regex() {
+        assert_eq!(raw_record("a b".to_string(), None), serde_json::json!({"message": "a b"}));
+    }
+
+    #[test]
+    fn raw_record_named_captures() {
+        let re = Regex::new(r"^(?P<level>[A-Z]+) (?P<code>\d+)?\s*(?P<rest>.*)$").unwrap();
+        assert_eq!(
+            raw_record("WARN disk full".to_string(), Some(&re)),
+            // `code` did not participate in the match: no field
+            serde_json::json!({"level": "WARN", "rest": "disk full", "message": "WARN disk full"})
+        );
+
+        // No match: only the message
+        assert_eq!(raw_record("warn".to_string(), Some(&re)), serde_json::json!({"message": "warn"}));
+    }
+
+    #[test]
+    fn raw_record_message_capture_replaces_text() {
+        let re = Regex::new(r"^\[(?P<pid>\d+)\] (?P<message>.*)$").unwrap();
+        assert_eq!(
+            raw_record("[42] started".to_string(), Some(&re)),
+            serde_json::json!({"pid": "42", "message": "started"})
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
+
+/// A password or token. Its Debug output is redacted so it never reaches the logs.
+#[derive(Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            f.write_str("Secret(<empty>)")
+        } else {
+            f.write_str("Secret(***)")
+        }
+    }
+}
+
+/// Replace `${NAME}` by the value of the environment variable NAME in every string of
+/// a YAML document. An unset variable is an error; `$${` is kept as a literal `${`.
+/// Errors name the YAML key ("splunk.password"), never the value: it may be a secret.
+pub fn interpolate_env(value: &mut YamlValue) -> Result<(), String> {
+    interpolate_at(value, "")
+}
+
+fn interpolate_at(value: &mut YamlValue, path: &str) -> Result<(), String> {
+    match value {
+        YamlValue::String(s) if s.contains("${") => {
+            *s = interpolate_str(s).map_err(|e| if path.is_empty() { e } else { format!("{}: {}", path, e) })?
+        }
+        YamlValue::Sequence(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                interpolate_at(item, &format!("{}[{}]", path, i))?;
+            }
+        }
+        YamlValue::Mapping(map) => {
+            for (k, v) in map.iter_mut() {
+                let key = match k {
+                    YamlValue::String(k) => k.clone(),
+                    YamlValue::Number(n) => n.to_string(),
+                    YamlValue::Bool(b) => b.to_string(),
+                    _ => "?".to_string(),
+                };
+                let path = if path.is_empty() { key } else { format!("{}.{}", path, key) };
+                interpolate_at(v, &path)?;
+            }
+        }
+        YamlValue::Tagged(tagged) => interpolate_at(&mut tagged.value, path)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_str(s: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(pos) = rest.find("${") {
+        // "$${" escapes the substitution
+        if rest[..pos].ends_with('$') {
+            out.push_str(&rest[..pos - 1]);
+            out.push_str("${");
+            rest = &rest[pos + 2..];
+            continue;
+        }
+
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("unterminated '${{' at byte {}", s.len() - after.len() - 2))?;
+        let name = &after[..end];
+        match std::env::var(name) {
+            Ok(v) => out.push_str(&v),
+            Err(_) => return Err(format!("environment variable '{}' is not set", name)),
+        }
+        rest = &after[end + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Read a secret from a file (e.g. a mounted Docker/Kubernetes secret).
+/// Trailing line breaks are removed.
+pub fn read_secret_file(path: &Path) -> io::Result<Secret> {
+    let content = fs::read_to_string(path)?;
+    Ok(Secret(content.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Ask for a secret on the terminal without echo.
+/// Returns None when stdin is not a TTY (batch jobs) or the prompt fails.
+pub fn prompt_secret(prompt: &str) -> Option<Secret> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    rpassword::prompt_password(prompt).ok().map(Secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Set by cargo for the test binary
+    const VAR: &str = "CARGO_PKG_NAME";
+    const VALUE: &str = env!("CARGO_PKG_NAME");
+    const UNSET: &str = "JSON2SPLUNK_TEST_UNSET_VARIABLE";
+
+    #[test]
+    fn interpolate_variables() {
+        assert_eq!(interpolate_str(&format!("${{{}}}", VAR)).unwrap(), VALUE);
+        assert_eq!(
+            interpolate_str(&format!("a ${{{0}}}-${{{0}}} b", VAR)).unwrap(),
+            format!("a {0}-{0} b", VALUE)
+        );
+        assert_eq!(interpolate_str("no variable, $ alone, {braces}").unwrap(), "no variable, $ alone, {braces}");
+    }
+
+    #[test]
+    fn interpolate_escape() {
+        assert_eq!(interpolate_str(&format!("$${{{}}}", VAR)).unwrap(), format!("${{{}}}", VAR));
+        // An escaped reference is not looked up, even when unset
+        assert_eq!(interpolate_str(&format!("p$${{{}}}", UNSET)).unwrap(), format!("p${{{}}}", UNSET));
+        // Escape followed by a real reference
+        assert_eq!(
+            interpolate_str(&format!("$${{x}} ${{{}}}", VAR)).unwrap(),
+            format!("${{x}} {}", VALUE)
+        );
+        assert_eq!(interpolate_str("$${").unwrap(), "${");
+    }
+
+    #[test]
+    fn interpolate_errors() {
+        let err = interpolate_str(&format!("${{{}}}", UNSET)).unwrap_err();
+        assert!(err.contains(UNSET), "{}", err);
+        assert_eq!(interpolate_str("${").unwrap_err(), "unterminated '${' at byte 0");
+        assert!(interpolate_str(&format!("${{{}", VAR)).unwrap_err().contains("unterminated"));
+        assert!(interpolate_str("${}").is_err());
+    }
+
+    #[test]
+    fn interpolate_yaml_document() {
+        let mut doc: YamlValue = serde_yaml::from_str(&format!(
+            "splunk:\n  user: ${{{0}}}\n  port: 8089\n  hosts: [a, '${{{0}}}']\n  token: !secret '$${{T}}'\n",
+            VAR
+        ))
+        .unwrap();
+        interpolate_env(&mut doc).unwrap();
+
+        let expected: YamlValue = serde_yaml::from_str(&format!(
+            "splunk:\n  user: {0}\n  port: 8089\n  hosts: [a, '{0}']\n  token: !secret '${{T}}'\n",
+            VALUE
+        ))
+        .unwrap();
+        assert_eq!(doc, expected);
+
+        let mut doc: YamlValue = serde_yaml::from_str(&format!("a: [ok, '${{{}}}']", UNSET)).unwrap();
+        assert!(interpolate_env(&mut doc).is_err());
+    }
+    #[test]
+    fn errors_do_not_show_the_value() {
+        let secret = "s3cr3t-${pa55";
+        let err = interpolate_str(secret).unwrap_err();
+        assert_eq!(err, "unterminated '${' at byte 7");
+        assert!(!err.contains("s3cr3t") && !err.contains("pa55"), "{}", err);
+
+        let mut doc: YamlValue =
+            serde_yaml::from_str(&format!("splunk:\n  hosts: [a]\n  password: '{}'\n", secret)).unwrap();
+        let err = interpolate_env(&mut doc).unwrap_err();
+        assert_eq!(err, "splunk.password: unterminated '${' at byte 7");
+
+        let mut doc: YamlValue = serde_yaml::from_str(&format!("hosts: [a, '{}']", secret)).unwrap();
+        assert_eq!(interpolate_env(&mut doc).unwrap_err(), "hosts[1]: unterminated '${' at byte 7");
+    }
+}
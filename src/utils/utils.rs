@@ -7,8 +7,10 @@ use xxhash_rust::xxh3::xxh3_64;
 
 /// Streaming lossy UTF-8 decoder:
 /// - reads raw bytes from `inner`
-/// - converts them with `String::from_utf8_lossy`
-/// - strips BOM + NULs per chunk
+/// - decodes them as UTF-8, replacing invalid sequences with U+FFFD
+/// - carries a multi-byte character split across two chunks over to the next chunk,
+///   so it is decoded exactly as if the whole file had been read at once
+/// - strips the BOM at the start of the stream, and NULs
 /// - exposes UTF-8 bytes via `Read`
 ///
 /// This lets us feed non-UTF-8 CSV files to `csv::Reader` without
@@ -16,8 +18,13 @@ use xxhash_rust::xxh3::xxh3_64;
 pub struct LossyUtf8Reader<R: IoRead> {
     inner: R,
     in_buf: Vec<u8>,
+    /// Bytes of an incomplete UTF-8 sequence at the end of the previous chunk (at most 3)
+    pending: Vec<u8>,
     out_buf: Vec<u8>,
     out_pos: usize,
+    /// Nothing decoded yet: a BOM is still expected
+    at_start: bool,
+    eof: bool,
 }
 
 impl<R: IoRead> LossyUtf8Reader<R> {
@@ -26,44 +33,92 @@ impl<R: IoRead> LossyUtf8Reader<R> {
         Self {
             inner,
             in_buf: vec![0u8; 8192], // chunk size (8 KiB)
+            pending: Vec::with_capacity(4),
             out_buf: Vec::new(),
             out_pos: 0,
+            at_start: true,
+            eof: false,
         }
     }
-}
 
-impl<R: IoRead> IoRead for LossyUtf8Reader<R> {
-    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
-        // Serve leftover UTF-8 bytes first
-        if self.out_pos < self.out_buf.len() {
-            let remaining = self.out_buf.len() - self.out_pos;
-            let to_copy = remaining.min(out.len());
-            out[..to_copy]
-                .copy_from_slice(&self.out_buf[self.out_pos..self.out_pos + to_copy]);
-            self.out_pos += to_copy;
-            return Ok(to_copy);
+    /// Decode `data` into `out_buf`. An incomplete sequence at the end of `data` is kept
+    /// in `pending`, unless the stream is over (`last`) where it becomes U+FFFD.
+    fn decode_chunk(&mut self, data: &[u8], last: bool) {
+        let mut rest = data;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(s) => {
+                    self.push_str(s);
+                    break;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    // The prefix was just validated
+                    self.push_str(std::str::from_utf8(&rest[..valid]).unwrap_or_default());
+                    match e.error_len() {
+                        // Invalid sequence: replace it and continue after it
+                        Some(len) => {
+                            self.push_str("\u{fffd}");
+                            rest = &rest[valid + len..];
+                        }
+                        // Truncated sequence at the end of the chunk
+                        None => {
+                            if last {
+                                self.push_str("\u{fffd}");
+                            } else {
+                                self.pending.extend_from_slice(&rest[valid..]);
+                            }
+                            break;
+                        }
+                    }
+                }
+            }
         }
+    }
 
-        // Read new raw bytes from inner
-        let n = self.inner.read(&mut self.in_buf)?;
-        if n == 0 {
-            return Ok(0); // EOF
+    fn push_str(&mut self, mut s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        if self.at_start {
+            self.at_start = false;
+            s = s.strip_prefix('\u{feff}').unwrap_or(s);
         }
+        self.out_buf
+            .extend(s.bytes().filter(|&b| b != 0));
+    }
+}
 
-        // Lossy UTF-8 decode
-        let mut s = String::from_utf8_lossy(&self.in_buf[..n]).into_owned();
+impl<R: IoRead> IoRead for LossyUtf8Reader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            // Serve leftover UTF-8 bytes first
+            if self.out_pos < self.out_buf.len() {
+                let remaining = self.out_buf.len() - self.out_pos;
+                let to_copy = remaining.min(out.len());
+                out[..to_copy]
+                    .copy_from_slice(&self.out_buf[self.out_pos..self.out_pos + to_copy]);
+                self.out_pos += to_copy;
+                return Ok(to_copy);
+            }
 
-        // Strip BOM + NULs per chunk (streaming)
-        s.retain(|c| c != '\u{feff}' && c != '\u{0}');
+            if self.eof {
+                return Ok(0);
+            }
+
+            // Read new raw bytes from inner
+            let n = self.inner.read(&mut self.in_buf)?;
+            let last = n == 0;
+            self.eof = last;
 
-        self.out_buf.clear();
-        self.out_buf.extend_from_slice(s.as_bytes());
-        self.out_pos = 0;
+            self.out_buf.clear();
+            self.out_pos = 0;
 
-        let to_copy = self.out_buf.len().min(out.len());
-        out[..to_copy].copy_from_slice(&self.out_buf[..to_copy]);
-        self.out_pos = to_copy;
-        Ok(to_copy)
+            // Prepend the incomplete sequence carried over from the previous chunk
+            let mut data = std::mem::take(&mut self.pending);
+            data.extend_from_slice(&self.in_buf[..n]);
+            self.decode_chunk(&data, last);
+        }
     }
 }
 
@@ -207,4 +262,129 @@ pub fn is_valid_hec_time(ts: f64) -> bool {
     }
 
     true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader handing out its data in chunks of random sizes (never 0 before the end).
+    struct ChunkedReader {
+        data: Vec<u8>,
+        pos: usize,
+        rng: fastrand::Rng,
+        max_chunk: usize,
+    }
+
+    impl IoRead for ChunkedReader {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            let left = self.data.len() - self.pos;
+            if left == 0 || out.is_empty() {
+                return Ok(0);
+            }
+            let n = self.rng.usize(1..=self.max_chunk).min(left).min(out.len());
+            out[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    /// Decode `data` through the reader, with random read sizes on both sides.
+    fn decode(data: &[u8], rng: &mut fastrand::Rng, max_chunk: usize) -> Vec<u8> {
+        let inner = ChunkedReader {
+            data: data.to_vec(),
+            pos: 0,
+            rng: fastrand::Rng::with_seed(rng.u64(..)),
+            max_chunk,
+        };
+        let mut reader = LossyUtf8Reader::new(inner);
+        let mut out = Vec::new();
+        let mut buf = [0u8; 16];
+        loop {
+            let size = rng.usize(1..=buf.len());
+            let n = reader.read(&mut buf[..size]).unwrap();
+            if n == 0 {
+                return out;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    /// What the reader must produce: the lossy decoding of the whole input at once,
+    /// without its leading BOM and NULs.
+    fn expected(data: &[u8]) -> Vec<u8> {
+        let text = String::from_utf8_lossy(data);
+        let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+        text.replace('\0', "").into_bytes()
+    }
+
+    /// Random bytes, biased towards multi-byte characters and broken sequences.
+    fn random_bytes(rng: &mut fastrand::Rng) -> Vec<u8> {
+        const PIECES: &[&[u8]] = &[
+            b"a",
+            b"\n",
+            b"\0",
+            "é".as_bytes(),
+            "€".as_bytes(),
+            "😀".as_bytes(),
+            "\u{feff}".as_bytes(),
+            b"\xc3",             // truncated 2-byte sequence
+            b"\xe2\x82",         // truncated 3-byte sequence
+            b"\xf0\x9f\x98",     // truncated 4-byte sequence
+            b"\x80",             // lone continuation byte
+            b"\xff",             // never valid
+            b"\xed\xa0\x80",     // UTF-16 surrogate
+            b"\xc0\xaf",         // overlong encoding
+        ];
+        let mut data = Vec::new();
+        if rng.bool() {
+            data.extend_from_slice("\u{feff}".as_bytes());
+        }
+        for _ in 0..rng.usize(0..64) {
+            if rng.u8(..4) == 0 {
+                data.push(rng.u8(..));
+            } else {
+                data.extend_from_slice(PIECES[rng.usize(..PIECES.len())]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn lossy_reader_matches_whole_input_decoding() {
+        let mut rng = fastrand::Rng::with_seed(0x5eed);
+        for round in 0..5000 {
+            let data = random_bytes(&mut rng);
+            let max_chunk = rng.usize(1..=8);
+            assert_eq!(
+                decode(&data, &mut rng, max_chunk),
+                expected(&data),
+                "round {}, input {:x?}, chunks up to {} bytes",
+                round,
+                data,
+                max_chunk
+            );
+        }
+    }
+
+    #[test]
+    fn lossy_reader_sequences_straddling_chunks() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        let cases: &[&[u8]] = &[
+            // Valid characters split at every position with 1-byte reads
+            "\u{feff}é€😀x".as_bytes(),
+            // Truncated sequence followed by ASCII, by another lead byte, then at the very end
+            b"\xe2\x82a\xf0\x9f\xc3\xa9\xf0\x9f\x98",
+            // A BOM split across chunks, followed by NULs
+            b"\xef\xbb\xbf\0a\0",
+            // No leading BOM: a later one is kept
+            b"a\xef\xbb\xbf",
+            b"\xff\xef\xbb\xbf",
+        ];
+        for data in cases {
+            for max_chunk in 1..=4 {
+                assert_eq!(decode(data, &mut rng, max_chunk), expected(data), "input {:x?}", data);
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -1,23 +1,29 @@
 // src/vrl.rs
 use std::path::{Path, PathBuf};
 
-use log::warn;
+use log::{debug, warn};
 use serde_json::Value;
 use vrl::{
     compiler::{
         self,
         state::RuntimeState,
         Context as VrlContext,
+        ExpressionError,
+        Function,
         Program as VrlProgram,
         TargetValue,
         TimeZone as VrlTimeZone,
     },
     diagnostic::{Diagnostic, DiagnosticList, Formatter as DiagFormatter},
-    value::{Secrets, Value as VrlValue},
+    value::{ObjectMap, Secrets, Value as VrlValue},
 };
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex, OnceLock}; 
-static VRL_CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<VrlProgram>>>> = OnceLock::new();
+/// A compiled program with its source (needed to format runtime errors).
+type CachedScript = Arc<(VrlProgram, String)>;
+static VRL_CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedScript>>> = OnceLock::new();
+/// Standard library + DFIR functions (FILETIME, SID names, ...), built once for every compilation.
+static VRL_FUNCTIONS: OnceLock<Vec<Box<dyn Function>>> = OnceLock::new();
 
 /// Public alias so json2splunk.rs can use it.
 pub type VrlChain = Vec<(VrlProgram, String)>;
@@ -35,7 +41,11 @@ pub fn compile_vrl_chain(vrl_dir: Option<&Path>, normalize_paths: &[String]) ->
     // Initialize the cache if it hasn't been accessed yet
     let cache_lock = VRL_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
     
-    let functions = vrl::stdlib::all();
+    let functions = VRL_FUNCTIONS.get_or_init(|| {
+        let mut functions = vrl::stdlib::all();
+        functions.extend(crate::utils::vrl_functions::all());
+        functions
+    });
     let mut out = Vec::new();
 
     for p in normalize_paths {
@@ -48,11 +58,10 @@ pub fn compile_vrl_chain(vrl_dir: Option<&Path>, normalize_paths: &[String]) ->
         // 1. Check Cache
         {
             let cache = cache_lock.lock().unwrap();
-            if let Some(program) = cache.get(&path) {
-                // If found, clone the Arc (cheap) and continue
-                // We don't store the source string in cache for simplicity, 
-                // but we need it for the tuple. We'll pass a placeholder or read it lightly.
-                out.push((program.as_ref().clone(), p.clone())); 
+            if let Some(cached) = cache.get(&path) {
+                // If found, reuse the program and its source
+                let (program, source) = cached.as_ref();
+                out.push((program.clone(), source.clone()));
                 continue;
             }
         } // Drop lock here to do IO
@@ -66,9 +75,9 @@ pub fn compile_vrl_chain(vrl_dir: Option<&Path>, normalize_paths: &[String]) ->
             }
         };
 
-        match compiler::compile(&source, &functions) {
+        match compiler::compile(&source, functions) {
             Ok(compiled) => {
-                let prog_arc = Arc::new(compiled.program.clone());
+                let prog_arc = Arc::new((compiled.program.clone(), source.clone()));
                 
                 // 3. Store in Cache
                 {
@@ -89,37 +98,140 @@ pub fn compile_vrl_chain(vrl_dir: Option<&Path>, normalize_paths: &[String]) ->
     out
 }
 
-/// Apply a VRL chain to a serde_json `Value`.
+/// Splunk routing set by a VRL script in `%splunk`, overriding the pattern settings.
+#[derive(Debug, Default, Clone)]
+pub struct SplunkOverrides {
+    pub index: Option<String>,
+    pub sourcetype: Option<String>,
+    pub host: Option<String>,
+    pub source: Option<String>,
+    /// Epoch seconds
+    pub time: Option<f64>,
+}
+
+impl SplunkOverrides {
+    /// Read `%splunk.*` from the metadata left by the chain. None when no script set it.
+    fn from_metadata(metadata: &VrlValue) -> Option<Self> {
+        let splunk = metadata.as_object()?.get("splunk")?.as_object()?;
+        let text = |key: &str| splunk.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+        // %splunk.time: a timestamp (e.g. parse_timestamp!) or epoch seconds
+        let time = match splunk.get("time") {
+            Some(VrlValue::Timestamp(ts)) => Some(ts.timestamp_micros() as f64 / 1_000_000.0),
+            Some(VrlValue::Integer(i)) => Some(*i as f64),
+            Some(VrlValue::Float(f)) => Some(f.into_inner()),
+            Some(other) => {
+                debug!("Ignoring %splunk.time of type {}: expected a timestamp or epoch seconds", other.kind_str());
+                None
+            }
+            None => None,
+        };
+
+        Some(SplunkOverrides {
+            index: text("index"),
+            sourcetype: text("sourcetype"),
+            host: text("host"),
+            source: text("source"),
+            time,
+        })
+    }
+}
+
+/// VRL metadata of an event: %file_path, %line, %source (pattern name), %artifact and
+/// %host (from host_rex).
+pub fn event_metadata(file_path: &str, line: usize, source: &str, artifact: &str, host: &str) -> VrlValue {
+    let mut metadata = ObjectMap::new();
+    metadata.insert("file_path".into(), VrlValue::from(file_path));
+    metadata.insert("line".into(), VrlValue::Integer(line as i64));
+    metadata.insert("source".into(), VrlValue::from(source));
+    metadata.insert("artifact".into(), VrlValue::from(artifact));
+    metadata.insert("host".into(), VrlValue::from(host));
+    VrlValue::Object(metadata)
+}
+
+/// Why a VRL chain did not produce an event.
+#[derive(Debug)]
+pub enum VrlChainError {
+    /// A script called `abort`: the event is dropped on purpose
+    Aborted,
+    /// A script failed at runtime (formatted diagnostic)
+    Runtime(String),
+    /// The event cannot be converted to or from a VRL value
+    Invalid(String),
+}
+
+/// Apply a VRL chain to a serde_json `Value`, with `metadata` as `%` (kept from one
+/// script of the chain to the next).
 /// Returns:
-///   - Some(new_value) on success
-///   - None if any program in the chain fails at runtime
-pub fn apply_vrl_chain_to_record(val: Value, chain: &[(VrlProgram, String)]) -> Option<Value> {
+///   - Some((new_value, %splunk overrides)) on success
+///   - None if a script aborts or any program in the chain fails at runtime
+pub fn apply_vrl_chain_to_record(
+    val: Value,
+    chain: &[(VrlProgram, String)],
+    metadata: VrlValue,
+) -> Option<(Value, Option<SplunkOverrides>)> {
+    match run_vrl_chain(val, chain, metadata, true) {
+        Ok(out) => Some(out),
+        Err(VrlChainError::Aborted) => {
+            debug!("Event dropped by a VRL abort");
+            None
+        }
+        Err(VrlChainError::Runtime(err)) => {
+            warn!("VRL runtime error:\n{}", err);
+            None
+        }
+        Err(VrlChainError::Invalid(err)) => {
+            warn!("VRL: {}", err);
+            None
+        }
+    }
+}
+
+/// Same as `apply_vrl_chain_to_record`, telling an `abort` from an error. Runtime errors
+/// are formatted with the script source (ANSI colors when `colored`).
+pub fn run_vrl_chain(
+    val: Value,
+    chain: &[(VrlProgram, String)],
+    metadata: VrlValue,
+    colored: bool,
+) -> Result<(Value, Option<SplunkOverrides>), VrlChainError> {
     if chain.is_empty() {
-        return Some(val);
+        return Ok((val, None));
     }
 
-    let mut vrl_val: VrlValue = serde_json::from_value(val).ok()?;
+    let mut vrl_val: VrlValue =
+        serde_json::from_value(val).map_err(|e| VrlChainError::Invalid(format!("invalid event: {}", e)))?;
+    let mut metadata = metadata;
     let tz = VrlTimeZone::default();
 
     for (prog, src) in chain {
         let mut target = TargetValue {
             value: vrl_val,
-            metadata: VrlValue::Object(Default::default()),
+            metadata,
             secrets: Secrets::default(),
         };
         let mut state = RuntimeState::default();
         let mut ctx = VrlContext::new(&mut target, &mut state, &tz);
 
-        if let Err(err) = prog.resolve(&mut ctx) {
-            let diag: Diagnostic = err.into();
-            let diag_list = DiagnosticList::from(vec![diag]);
-            let formatter = DiagFormatter::new(src, diag_list).colored();
-            warn!("VRL runtime error:\n{}", formatter);
-            return None;
+        match prog.resolve(&mut ctx) {
+            // `return` ends the script normally
+            Ok(_) | Err(ExpressionError::Return { .. }) => {}
+            Err(ExpressionError::Abort { .. }) => return Err(VrlChainError::Aborted),
+            Err(err) => {
+                let diag: Diagnostic = err.into();
+                let diag_list = DiagnosticList::from(vec![diag]);
+                let formatter = DiagFormatter::new(src, diag_list);
+                let formatter = if colored { formatter.colored() } else { formatter };
+                return Err(VrlChainError::Runtime(formatter.to_string()));
+            }
         }
 
         vrl_val = target.value;
+        metadata = target.metadata;
     }
 
-    serde_json::to_value(&vrl_val).ok()
+    let overrides = SplunkOverrides::from_metadata(&metadata);
+    serde_json::to_value(&vrl_val)
+        .map(|v| (v, overrides))
+        .map_err(|e| VrlChainError::Invalid(format!("cannot convert the VRL output to JSON: {}", e)))
 }
@@ -0,0 +1,828 @@
+// src/utils/vrl_functions.rs
+//! VRL functions for forensic data, registered next to the VRL standard library
+//! for every normalize script:
+//!
+//!   filetime_to_timestamp(value)              Windows FILETIME (100 ns ticks since 1601) → timestamp
+//!   ole_date_to_timestamp(value)              OLE automation date (days since 1899-12-30) → timestamp
+//!   sid_name(value)                           well-known SID → name
+//!   ntstatus_description(value)               NTSTATUS code → description
+//!   logon_type_description(value)             logon type → name
+//!   event_id_description(value, [provider])   Security/System/PowerShell/Sysmon event ID → description
+//!   parse_user_account_control(value)         userAccountControl → array of flag names
+//!   parse_windows_guid(value, [mixed_endian]) GUID bytes or hex dump → canonical GUID string
+//!   lookup(table, conditions)                 first row of a lookup table matching the conditions
+use chrono::{DateTime, Utc};
+use vrl::compiler::prelude::*;
+
+use crate::utils::lookup::{self, LookupTable};
+
+/// Seconds between 1601-01-01 (FILETIME epoch) and 1970-01-01.
+const FILETIME_EPOCH_OFFSET: i64 = 11_644_473_600;
+/// Seconds between 1899-12-30 (OLE date epoch) and 1970-01-01.
+const OLE_EPOCH_OFFSET: f64 = 2_209_161_600.0;
+
+/// Custom functions, to append to `vrl::stdlib::all()`.
+pub fn all() -> Vec<Box<dyn Function>> {
+    vec![
+        Box::new(DfirFunction {
+            identifier: "filetime_to_timestamp",
+            parameters: VALUE_NUMBER,
+            examples: &[Example {
+                title: "FILETIME",
+                source: "filetime_to_timestamp!(132539328000000000)",
+                result: Ok("t'2021-01-01T00:00:00Z'"),
+            }],
+            convert: filetime_to_timestamp,
+            type_def: || TypeDef::timestamp().or_null().fallible(),
+        }),
+        Box::new(DfirFunction {
+            identifier: "ole_date_to_timestamp",
+            parameters: &[Parameter {
+                keyword: "value",
+                kind: kind::FLOAT | kind::INTEGER | kind::BYTES,
+                required: true,
+            }],
+            examples: &[Example {
+                title: "OLE date",
+                source: "ole_date_to_timestamp!(44197.5)",
+                result: Ok("t'2021-01-01T12:00:00Z'"),
+            }],
+            convert: ole_date_to_timestamp,
+            type_def: || TypeDef::timestamp().fallible(),
+        }),
+        Box::new(DfirFunction {
+            identifier: "sid_name",
+            parameters: VALUE_ANY,
+            examples: &[Example {
+                title: "well-known SID",
+                source: r#"sid_name("S-1-5-18")"#,
+                result: Ok("Local System"),
+            }],
+            convert: sid_name,
+            type_def: || TypeDef::bytes().or_null().infallible(),
+        }),
+        Box::new(DfirFunction {
+            identifier: "ntstatus_description",
+            parameters: VALUE_ANY,
+            examples: &[Example {
+                title: "logon failure status",
+                source: r#"ntstatus_description("0xC000006A")"#,
+                result: Ok("Wrong password"),
+            }],
+            convert: ntstatus_description,
+            type_def: || TypeDef::bytes().or_null().infallible(),
+        }),
+        Box::new(DfirFunction {
+            identifier: "logon_type_description",
+            parameters: VALUE_ANY,
+            examples: &[Example {
+                title: "RDP logon",
+                source: "logon_type_description(10)",
+                result: Ok("RemoteInteractive"),
+            }],
+            convert: logon_type_description,
+            type_def: || TypeDef::bytes().or_null().infallible(),
+        }),
+        Box::new(DfirFunction {
+            identifier: "event_id_description",
+            parameters: &[
+                Parameter {
+                    keyword: "value",
+                    kind: kind::ANY,
+                    required: true,
+                },
+                Parameter {
+                    keyword: "provider",
+                    kind: kind::ANY,
+                    required: false,
+                },
+            ],
+            examples: &[Example {
+                title: "Sysmon event",
+                source: r#"event_id_description(3, "Microsoft-Windows-Sysmon")"#,
+                result: Ok("Network connection"),
+            }],
+            convert: event_id_description,
+            type_def: || TypeDef::bytes().or_null().infallible(),
+        }),
+        Box::new(DfirFunction {
+            identifier: "parse_user_account_control",
+            parameters: VALUE_NUMBER,
+            examples: &[Example {
+                title: "disabled account",
+                source: "parse_user_account_control!(514)",
+                result: Ok(r#"["ACCOUNTDISABLE", "NORMAL_ACCOUNT"]"#),
+            }],
+            convert: parse_user_account_control,
+            type_def: || TypeDef::array(Collection::from_unknown(Kind::bytes())).fallible(),
+        }),
+        Box::new(DfirFunction {
+            identifier: "parse_windows_guid",
+            parameters: &[
+                Parameter {
+                    keyword: "value",
+                    kind: kind::BYTES,
+                    required: true,
+                },
+                Parameter {
+                    keyword: "mixed_endian",
+                    kind: kind::BOOLEAN,
+                    required: false,
+                },
+            ],
+            examples: &[Example {
+                title: "GUID hex dump",
+                source: r#"parse_windows_guid!("33221100554477668899aabbccddeeff")"#,
+                result: Ok("00112233-4455-6677-8899-aabbccddeeff"),
+            }],
+            convert: parse_windows_guid,
+            type_def: || TypeDef::bytes().fallible(),
+        }),
+        Box::new(Lookup),
+    ]
+}
+
+/// Integer value, or its decimal / 0x-hexadecimal string form (e.g. a registry dump).
+const VALUE_NUMBER: &[Parameter] = &[Parameter {
+    keyword: "value",
+    kind: kind::INTEGER | kind::BYTES,
+    required: true,
+}];
+
+/// Any value: the description functions return null for what they cannot read, so a
+/// missing or unexpected field needs no error handling in the scripts.
+const VALUE_ANY: &[Parameter] = &[Parameter {
+    keyword: "value",
+    kind: kind::ANY,
+    required: true,
+}];
+
+/// Conversion of the resolved arguments: the value and the optional second parameter.
+type Convert = fn(Value, Option<Value>) -> Resolved;
+
+/// Function of this module: every one takes a value and at most one option, so a
+/// single definition covers them all.
+#[derive(Debug, Clone)]
+struct DfirFunction {
+    identifier: &'static str,
+    parameters: &'static [Parameter],
+    examples: &'static [Example],
+    convert: Convert,
+    type_def: fn() -> TypeDef,
+}
+
+impl Function for DfirFunction {
+    fn identifier(&self) -> &'static str {
+        self.identifier
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        self.parameters
+    }
+
+    fn examples(&self) -> &'static [Example] {
+        self.examples
+    }
+
+    fn compile(&self, _state: &TypeState, _ctx: &mut FunctionCompileContext, arguments: ArgumentList) -> Compiled {
+        let option = self.parameters.get(1).and_then(|p| arguments.optional(p.keyword));
+        Ok(DfirFunctionFn {
+            value: arguments.required("value"),
+            option,
+            convert: self.convert,
+            type_def: self.type_def,
+        }
+        .as_expr())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DfirFunctionFn {
+    value: Box<dyn Expression>,
+    option: Option<Box<dyn Expression>>,
+    convert: Convert,
+    type_def: fn() -> TypeDef,
+}
+
+impl FunctionExpression for DfirFunctionFn {
+    fn resolve(&self, ctx: &mut Context) -> Resolved {
+        let value = self.value.resolve(ctx)?;
+        let option = match self.option {
+            Some(ref option) => Some(option.resolve(ctx)?),
+            None => None,
+        };
+        (self.convert)(value, option)
+    }
+
+    fn type_def(&self, _: &TypeState) -> TypeDef {
+        (self.type_def)()
+    }
+}
+
+/// lookup("assets", {"host": .host.name}): the row of a table of the `lookups:` section
+/// (or --lookup) matching every condition, or null. The table name must be a literal, so a
+/// script using an unknown table fails to compile.
+#[derive(Debug, Clone, Copy)]
+struct Lookup;
+
+impl Function for Lookup {
+    fn identifier(&self) -> &'static str {
+        "lookup"
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[
+            Parameter {
+                keyword: "table",
+                kind: kind::BYTES,
+                required: true,
+            },
+            Parameter {
+                keyword: "conditions",
+                kind: kind::OBJECT,
+                required: true,
+            },
+        ]
+    }
+
+    fn examples(&self) -> &'static [Example] {
+        &[Example {
+            title: "asset inventory",
+            source: r#"lookup("assets", {"host": "WS-01"})"#,
+            result: Ok(r#"{"host": "WS-01", "owner": "finance"}"#),
+        }]
+    }
+
+    fn compile(&self, state: &TypeState, _ctx: &mut FunctionCompileContext, arguments: ArgumentList) -> Compiled {
+        let name = arguments.required_literal("table", state)?;
+        let table = name
+            .as_str()
+            .and_then(|n| lookup::table(&n))
+            .ok_or_else(|| -> Box<dyn DiagnosticMessage> {
+                Box::new(function::Error::InvalidArgument {
+                    keyword: "table",
+                    value: name.clone(),
+                    error: "unknown lookup table",
+                })
+            })?;
+
+        Ok(LookupFn {
+            table,
+            conditions: arguments.required("conditions"),
+        }
+        .as_expr())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LookupFn {
+    table: &'static LookupTable,
+    conditions: Box<dyn Expression>,
+}
+
+impl FunctionExpression for LookupFn {
+    fn resolve(&self, ctx: &mut Context) -> Resolved {
+        let row = match self.conditions.resolve(ctx)? {
+            Value::Object(conditions) => self.table.find(&conditions).cloned().map(Value::Object),
+            _ => None,
+        };
+        Ok(row.unwrap_or(Value::Null))
+    }
+
+    fn type_def(&self, _: &TypeState) -> TypeDef {
+        TypeDef::object(Collection::any()).or_null().infallible()
+    }
+}
+
+/// Integer of an integer or a decimal / 0x-hexadecimal string. Hexadecimal strings may use
+/// the full 64 bits (FILETIME, NTSTATUS written unsigned).
+fn to_integer(value: Value) -> Result<i64, ExpressionError> {
+    if let Value::Integer(i) = value {
+        return Ok(i);
+    }
+    let bytes = value.try_bytes()?;
+    let text = String::from_utf8_lossy(&bytes);
+    let text = text.trim();
+
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).map(|v| v as i64).ok(),
+        None => text.parse::<i64>().ok().or_else(|| text.parse::<u64>().ok().map(|v| v as i64)),
+    };
+    parsed.ok_or_else(|| format!("invalid integer: {:?}", text).into())
+}
+
+fn described(description: Option<&str>) -> Resolved {
+    Ok(description.map_or(Value::Null, Value::from))
+}
+
+fn filetime_to_timestamp(value: Value, _: Option<Value>) -> Resolved {
+    let ticks = to_integer(value)? as u64;
+    // 0 and 0x7FFFFFFFFFFFFFFF are "never" (e.g. an account that never logged on)
+    if ticks == 0 || ticks >= i64::MAX as u64 {
+        return Ok(Value::Null);
+    }
+
+    let secs = (ticks / 10_000_000) as i64 - FILETIME_EPOCH_OFFSET;
+    let nanos = (ticks % 10_000_000) as u32 * 100;
+    DateTime::<Utc>::from_timestamp(secs, nanos)
+        .map(Value::from)
+        .ok_or_else(|| format!("FILETIME out of range: {}", ticks).into())
+}
+
+fn ole_date_to_timestamp(value: Value, _: Option<Value>) -> Resolved {
+    let days = match value {
+        Value::Float(f) => f.into_inner(),
+        Value::Integer(i) => i as f64,
+        other => {
+            let bytes = other.try_bytes()?;
+            let text = String::from_utf8_lossy(&bytes);
+            text.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("invalid OLE date: {:?}", text))?
+        }
+    };
+    if !days.is_finite() {
+        return Err(format!("invalid OLE date: {}", days).into());
+    }
+
+    // Before 1899-12-30 the fraction still counts forward from midnight: -1.25 is 1899-12-29 06:00
+    let whole = days.trunc();
+    let fraction = (days - whole).abs();
+    let micros = ((whole + fraction) * 86_400.0 - OLE_EPOCH_OFFSET) * 1_000_000.0;
+    DateTime::<Utc>::from_timestamp_micros(micros.round() as i64)
+        .map(Value::from)
+        .ok_or_else(|| format!("OLE date out of range: {}", days).into())
+}
+
+/// Well-known SIDs (https://learn.microsoft.com/en-us/windows/win32/secauthz/well-known-sids).
+const WELL_KNOWN_SIDS: &[(&str, &str)] = &[
+    ("S-1-0-0", "Nobody"),
+    ("S-1-1-0", "Everyone"),
+    ("S-1-2-0", "Local"),
+    ("S-1-2-1", "Console Logon"),
+    ("S-1-3-0", "Creator Owner"),
+    ("S-1-3-1", "Creator Group"),
+    ("S-1-3-4", "Owner Rights"),
+    ("S-1-5-1", "Dialup"),
+    ("S-1-5-2", "Network"),
+    ("S-1-5-3", "Batch"),
+    ("S-1-5-4", "Interactive"),
+    ("S-1-5-6", "Service"),
+    ("S-1-5-7", "Anonymous Logon"),
+    ("S-1-5-9", "Enterprise Domain Controllers"),
+    ("S-1-5-10", "Self"),
+    ("S-1-5-11", "Authenticated Users"),
+    ("S-1-5-12", "Restricted Code"),
+    ("S-1-5-13", "Terminal Server Users"),
+    ("S-1-5-14", "Remote Interactive Logon"),
+    ("S-1-5-15", "This Organization"),
+    ("S-1-5-17", "IUSR"),
+    ("S-1-5-18", "Local System"),
+    ("S-1-5-19", "Local Service"),
+    ("S-1-5-20", "Network Service"),
+    ("S-1-5-32-544", "Administrators"),
+    ("S-1-5-32-545", "Users"),
+    ("S-1-5-32-546", "Guests"),
+    ("S-1-5-32-547", "Power Users"),
+    ("S-1-5-32-548", "Account Operators"),
+    ("S-1-5-32-549", "Server Operators"),
+    ("S-1-5-32-550", "Print Operators"),
+    ("S-1-5-32-551", "Backup Operators"),
+    ("S-1-5-32-552", "Replicators"),
+    ("S-1-5-32-554", "Pre-Windows 2000 Compatible Access"),
+    ("S-1-5-32-555", "Remote Desktop Users"),
+    ("S-1-5-32-556", "Network Configuration Operators"),
+    ("S-1-5-32-558", "Performance Monitor Users"),
+    ("S-1-5-32-559", "Performance Log Users"),
+    ("S-1-5-32-562", "Distributed COM Users"),
+    ("S-1-5-32-568", "IIS_IUSRS"),
+    ("S-1-5-32-569", "Cryptographic Operators"),
+    ("S-1-5-32-573", "Event Log Readers"),
+    ("S-1-5-32-578", "Hyper-V Administrators"),
+    ("S-1-5-32-580", "Remote Management Users"),
+    ("S-1-5-64-10", "NTLM Authentication"),
+    ("S-1-5-64-14", "SChannel Authentication"),
+    ("S-1-5-64-21", "Digest Authentication"),
+    ("S-1-5-80-0", "All Services"),
+    ("S-1-5-113", "Local Account"),
+    ("S-1-5-114", "Local Account and Member of Administrators Group"),
+    ("S-1-15-2-1", "All Application Packages"),
+    ("S-1-16-0", "Untrusted Mandatory Level"),
+    ("S-1-16-4096", "Low Mandatory Level"),
+    ("S-1-16-8192", "Medium Mandatory Level"),
+    ("S-1-16-8448", "Medium Plus Mandatory Level"),
+    ("S-1-16-12288", "High Mandatory Level"),
+    ("S-1-16-16384", "System Mandatory Level"),
+    ("S-1-16-20480", "Protected Process Mandatory Level"),
+];
+
+/// Well-known relative IDs of domain and local machine SIDs (S-1-5-21-<authority>-<rid>).
+const WELL_KNOWN_RIDS: &[(&str, &str)] = &[
+    ("500", "Administrator"),
+    ("501", "Guest"),
+    ("502", "krbtgt"),
+    ("503", "DefaultAccount"),
+    ("504", "WDAGUtilityAccount"),
+    ("512", "Domain Admins"),
+    ("513", "Domain Users"),
+    ("514", "Domain Guests"),
+    ("515", "Domain Computers"),
+    ("516", "Domain Controllers"),
+    ("517", "Cert Publishers"),
+    ("518", "Schema Admins"),
+    ("519", "Enterprise Admins"),
+    ("520", "Group Policy Creator Owners"),
+    ("521", "Read-only Domain Controllers"),
+    ("522", "Cloneable Domain Controllers"),
+    ("525", "Protected Users"),
+    ("526", "Key Admins"),
+    ("527", "Enterprise Key Admins"),
+    ("553", "RAS and IAS Servers"),
+    ("571", "Allowed RODC Password Replication Group"),
+    ("572", "Denied RODC Password Replication Group"),
+];
+
+fn sid_name(value: Value, _: Option<Value>) -> Resolved {
+    let Ok(bytes) = value.try_bytes() else {
+        return Ok(Value::Null);
+    };
+    let sid = String::from_utf8_lossy(&bytes).trim().to_uppercase();
+
+    if let Some((_, name)) = WELL_KNOWN_SIDS.iter().find(|(s, _)| *s == sid) {
+        return described(Some(name));
+    }
+    // S-1-5-21-<3 sub-authorities>-<rid>
+    let name = sid
+        .strip_prefix("S-1-5-21-")
+        .filter(|rest| rest.split('-').count() == 4)
+        .and_then(|rest| rest.rsplit('-').next())
+        .and_then(|rid| WELL_KNOWN_RIDS.iter().find(|(r, _)| *r == rid))
+        .map(|(_, name)| *name);
+    described(name)
+}
+
+/// NTSTATUS codes of logon failures (4625, 4771, 4776) and common API errors.
+const NTSTATUS_CODES: &[(u32, &str)] = &[
+    (0x0000_0000, "Success"),
+    (0x8000_0005, "Buffer overflow"),
+    (0xC000_0001, "Unsuccessful"),
+    (0xC000_0005, "Access violation"),
+    (0xC000_0008, "Invalid handle"),
+    (0xC000_000D, "Invalid parameter"),
+    (0xC000_0017, "Not enough memory"),
+    (0xC000_0022, "Access denied"),
+    (0xC000_0034, "Object name not found"),
+    (0xC000_0035, "Object name collision"),
+    (0xC000_003A, "Object path not found"),
+    (0xC000_0043, "Sharing violation"),
+    (0xC000_005E, "No logon servers available"),
+    (0xC000_0061, "Privilege not held"),
+    (0xC000_0062, "Invalid account name"),
+    (0xC000_0063, "User already exists"),
+    (0xC000_0064, "User name does not exist"),
+    (0xC000_006A, "Wrong password"),
+    (0xC000_006D, "Logon failure: unknown user name or bad password"),
+    (0xC000_006E, "Account restriction"),
+    (0xC000_006F, "Logon outside authorized hours"),
+    (0xC000_0070, "Logon from unauthorized workstation"),
+    (0xC000_0071, "Password expired"),
+    (0xC000_0072, "Account disabled"),
+    (0xC000_009A, "Insufficient resources"),
+    (0xC000_00BB, "Not supported"),
+    (0xC000_00DC, "SAM server in wrong state"),
+    (0xC000_0133, "Clock skew between client and domain controller"),
+    (0xC000_015B, "Logon type not granted"),
+    (0xC000_018C, "Trust relationship failure"),
+    (0xC000_0192, "NetLogon service not started"),
+    (0xC000_0193, "Account expired"),
+    (0xC000_0224, "Password must change at next logon"),
+    (0xC000_0225, "Windows bug, not a risk"),
+    (0xC000_0234, "Account locked out"),
+    (0xC000_02EE, "An error occurred during logon"),
+    (0xC000_0371, "Local account store does not contain secret material"),
+    (0xC000_0380, "Smart card wrong PIN"),
+    (0xC000_0413, "Logon blocked by authentication firewall"),
+];
+
+fn ntstatus_description(value: Value, _: Option<Value>) -> Resolved {
+    // NTSTATUS are 32 bits; a signed rendering (-1073741715) is the same code
+    let code = to_integer(value).ok().map(|c| c as u32);
+    let description = code.and_then(|c| NTSTATUS_CODES.iter().find(|(k, _)| *k == c)).map(|(_, d)| *d);
+    described(description)
+}
+
+const LOGON_TYPES: &[(i64, &str)] = &[
+    (0, "System"),
+    (2, "Interactive"),
+    (3, "Network"),
+    (4, "Batch"),
+    (5, "Service"),
+    (7, "Unlock"),
+    (8, "NetworkCleartext"),
+    (9, "NewCredentials"),
+    (10, "RemoteInteractive"),
+    (11, "CachedInteractive"),
+    (12, "CachedRemoteInteractive"),
+    (13, "CachedUnlock"),
+];
+
+fn logon_type_description(value: Value, _: Option<Value>) -> Resolved {
+    let logon_type = to_integer(value).ok();
+    let description = logon_type.and_then(|t| LOGON_TYPES.iter().find(|(k, _)| *k == t)).map(|(_, d)| *d);
+    described(description)
+}
+
+/// Security, System and PowerShell events of interest in an investigation.
+const WINDOWS_EVENT_IDS: &[(i64, &str)] = &[
+    (104, "Event log cleared"),
+    (1102, "Security audit log cleared"),
+    (4103, "PowerShell module logging"),
+    (4104, "PowerShell script block logging"),
+    (4608, "Windows is starting up"),
+    (4616, "System time changed"),
+    (4624, "Successful logon"),
+    (4625, "Failed logon"),
+    (4634, "Logoff"),
+    (4647, "User initiated logoff"),
+    (4648, "Logon with explicit credentials"),
+    (4656, "Handle to an object requested"),
+    (4657, "Registry value modified"),
+    (4661, "Handle to an object requested (SAM/AD)"),
+    (4662, "Operation performed on an object"),
+    (4663, "Attempt to access an object"),
+    (4672, "Special privileges assigned to new logon"),
+    (4673, "Privileged service called"),
+    (4688, "Process created"),
+    (4689, "Process exited"),
+    (4697, "Service installed"),
+    (4698, "Scheduled task created"),
+    (4699, "Scheduled task deleted"),
+    (4700, "Scheduled task enabled"),
+    (4701, "Scheduled task disabled"),
+    (4702, "Scheduled task updated"),
+    (4719, "System audit policy changed"),
+    (4720, "User account created"),
+    (4722, "User account enabled"),
+    (4723, "Password change attempted"),
+    (4724, "Password reset attempted"),
+    (4725, "User account disabled"),
+    (4726, "User account deleted"),
+    (4728, "Member added to security-enabled global group"),
+    (4732, "Member added to security-enabled local group"),
+    (4738, "User account changed"),
+    (4740, "User account locked out"),
+    (4756, "Member added to security-enabled universal group"),
+    (4767, "User account unlocked"),
+    (4768, "Kerberos TGT requested"),
+    (4769, "Kerberos service ticket requested"),
+    (4771, "Kerberos pre-authentication failed"),
+    (4776, "Credential validation (NTLM)"),
+    (4778, "Session reconnected"),
+    (4779, "Session disconnected"),
+    (4798, "Local group membership enumerated"),
+    (4799, "Security-enabled local group membership enumerated"),
+    (5140, "Network share accessed"),
+    (5145, "Network share object checked"),
+    (5156, "Connection allowed by Windows Filtering Platform"),
+    (7036, "Service state changed"),
+    (7040, "Service start type changed"),
+    (7045, "Service installed"),
+];
+
+const SYSMON_EVENT_IDS: &[(i64, &str)] = &[
+    (1, "Process creation"),
+    (2, "File creation time changed"),
+    (3, "Network connection"),
+    (4, "Sysmon service state changed"),
+    (5, "Process terminated"),
+    (6, "Driver loaded"),
+    (7, "Image loaded"),
+    (8, "CreateRemoteThread"),
+    (9, "RawAccessRead"),
+    (10, "Process accessed"),
+    (11, "File created"),
+    (12, "Registry object added or deleted"),
+    (13, "Registry value set"),
+    (14, "Registry object renamed"),
+    (15, "File stream created"),
+    (16, "Sysmon configuration change"),
+    (17, "Pipe created"),
+    (18, "Pipe connected"),
+    (19, "WMI event filter activity"),
+    (20, "WMI event consumer activity"),
+    (21, "WMI event consumer to filter activity"),
+    (22, "DNS query"),
+    (23, "File deleted (archived)"),
+    (24, "Clipboard changed"),
+    (25, "Process tampering"),
+    (26, "File delete logged"),
+    (27, "File block executable"),
+    (28, "File block shredding"),
+    (29, "File executable detected"),
+    (255, "Sysmon error"),
+];
+
+fn event_id_description(value: Value, provider: Option<Value>) -> Resolved {
+    let Ok(event_id) = to_integer(value) else {
+        return Ok(Value::Null);
+    };
+    // Sysmon reuses small IDs: its table only applies to its provider
+    let sysmon = match provider {
+        Some(Value::Bytes(ref provider)) => String::from_utf8_lossy(provider).to_lowercase().contains("sysmon"),
+        _ => false,
+    };
+    let table = if sysmon { SYSMON_EVENT_IDS } else { WINDOWS_EVENT_IDS };
+    described(table.iter().find(|(k, _)| *k == event_id).map(|(_, d)| *d))
+}
+
+/// userAccountControl flags (https://learn.microsoft.com/en-us/troubleshoot/windows-server/active-directory/useraccountcontrol-manipulate-account-properties).
+const UAC_FLAGS: &[(i64, &str)] = &[
+    (0x0000_0001, "SCRIPT"),
+    (0x0000_0002, "ACCOUNTDISABLE"),
+    (0x0000_0008, "HOMEDIR_REQUIRED"),
+    (0x0000_0010, "LOCKOUT"),
+    (0x0000_0020, "PASSWD_NOTREQD"),
+    (0x0000_0040, "PASSWD_CANT_CHANGE"),
+    (0x0000_0080, "ENCRYPTED_TEXT_PWD_ALLOWED"),
+    (0x0000_0100, "TEMP_DUPLICATE_ACCOUNT"),
+    (0x0000_0200, "NORMAL_ACCOUNT"),
+    (0x0000_0800, "INTERDOMAIN_TRUST_ACCOUNT"),
+    (0x0000_1000, "WORKSTATION_TRUST_ACCOUNT"),
+    (0x0000_2000, "SERVER_TRUST_ACCOUNT"),
+    (0x0001_0000, "DONT_EXPIRE_PASSWORD"),
+    (0x0002_0000, "MNS_LOGON_ACCOUNT"),
+    (0x0004_0000, "SMARTCARD_REQUIRED"),
+    (0x0008_0000, "TRUSTED_FOR_DELEGATION"),
+    (0x0010_0000, "NOT_DELEGATED"),
+    (0x0020_0000, "USE_DES_KEY_ONLY"),
+    (0x0040_0000, "DONT_REQ_PREAUTH"),
+    (0x0080_0000, "PASSWORD_EXPIRED"),
+    (0x0100_0000, "TRUSTED_TO_AUTH_FOR_DELEGATION"),
+    (0x0400_0000, "PARTIAL_SECRETS_ACCOUNT"),
+];
+
+fn parse_user_account_control(value: Value, _: Option<Value>) -> Resolved {
+    let flags = to_integer(value)?;
+    Ok(Value::Array(
+        UAC_FLAGS
+            .iter()
+            .filter(|(bit, _)| flags & bit != 0)
+            .map(|(_, name)| Value::from(*name))
+            .collect(),
+    ))
+}
+
+fn parse_windows_guid(value: Value, mixed_endian: Option<Value>) -> Resolved {
+    // Windows stores the first three fields little-endian (GUID structure in memory);
+    // mixed_endian: false reads the 16 bytes in RFC 4122 (big-endian) order
+    let mixed_endian = match mixed_endian {
+        Some(v) => v.try_boolean()?,
+        None => true,
+    };
+    let bytes = value.try_bytes()?;
+
+    let mut guid = match <[u8; 16]>::try_from(bytes.as_ref()) {
+        // Raw bytes, e.g. from decode_base64
+        Ok(raw) => raw,
+        Err(_) => {
+            let text = String::from_utf8_lossy(&bytes);
+            let text = text.trim().trim_start_matches('{').trim_end_matches('}');
+            // Already in canonical form: the byte order is resolved
+            if is_canonical_guid(text) {
+                return Ok(Value::from(text.to_lowercase()));
+            }
+            let hex: String = text.chars().filter(|c| !matches!(c, ' ' | ':' | '-')).collect();
+            decode_hex_guid(&hex).ok_or_else(|| format!("invalid GUID: {:?}", text))?
+        }
+    };
+
+    if mixed_endian {
+        guid[0..4].reverse();
+        guid[4..6].reverse();
+        guid[6..8].reverse();
+    }
+    let hex: String = guid.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(Value::from(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )))
+}
+
+/// 8-4-4-4-12 hexadecimal digits.
+fn is_canonical_guid(text: &str) -> bool {
+    let groups: Vec<&str> = text.split('-').collect();
+    groups.len() == 5
+        && groups.iter().zip([8, 4, 4, 4, 12]).all(|(g, len)| g.len() == len && g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// 32 hexadecimal digits (a byte dump) into 16 bytes.
+fn decode_hex_guid(hex: &str) -> Option<[u8; 16]> {
+    if hex.len() != 32 || !hex.is_ascii() {
+        return None;
+    }
+    let mut guid = [0u8; 16];
+    for (i, byte) in guid.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(guid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp(rfc3339: &str) -> Value {
+        Value::from(DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc))
+    }
+
+    fn float(f: f64) -> Value {
+        Value::from_f64_or_zero(f)
+    }
+
+    fn names(value: Value) -> Vec<String> {
+        match value {
+            Value::Array(items) => items.iter().map(|v| v.as_str().unwrap().to_string()).collect(),
+            other => panic!("expected an array, got {}", other),
+        }
+    }
+
+    #[test]
+    fn filetime() {
+        let convert = |v: Value| filetime_to_timestamp(v, None).unwrap();
+        assert_eq!(convert(Value::Integer(116_444_736_000_000_000)), timestamp("1970-01-01T00:00:00Z"));
+        assert_eq!(convert(Value::Integer(133_485_408_000_000_001)), timestamp("2024-01-01T00:00:00.0000001Z"));
+        assert_eq!(convert(Value::from("133485408000000000")), timestamp("2024-01-01T00:00:00Z"));
+        assert_eq!(convert(Value::from("0x01DA3C4C6B0C4000")), convert(Value::Integer(0x01DA_3C4C_6B0C_4000)));
+        assert_eq!(convert(Value::Integer(1)), timestamp("1601-01-01T00:00:00.0000001Z"));
+        // "Never"
+        assert_eq!(convert(Value::Integer(0)), Value::Null);
+        assert_eq!(convert(Value::Integer(i64::MAX)), Value::Null);
+        assert_eq!(convert(Value::from("0xFFFFFFFFFFFFFFFF")), Value::Null);
+        assert!(filetime_to_timestamp(Value::from("yesterday"), None).is_err());
+    }
+
+    #[test]
+    fn ole_date() {
+        let convert = |v: Value| ole_date_to_timestamp(v, None).unwrap();
+        assert_eq!(convert(Value::Integer(0)), timestamp("1899-12-30T00:00:00Z"));
+        assert_eq!(convert(Value::Integer(25_569)), timestamp("1970-01-01T00:00:00Z"));
+        assert_eq!(convert(float(45_292.5)), timestamp("2024-01-01T12:00:00Z"));
+        assert_eq!(convert(Value::from(" 45292.25 ")), timestamp("2024-01-01T06:00:00Z"));
+        // Negative dates: the fraction counts forward from midnight of the day
+        assert_eq!(convert(float(-1.25)), timestamp("1899-12-29T06:00:00Z"));
+        assert_eq!(convert(float(-1.0)), timestamp("1899-12-29T00:00:00Z"));
+        assert_eq!(convert(float(-0.5)), timestamp("1899-12-30T12:00:00Z"));
+        assert!(ole_date_to_timestamp(Value::from("noon"), None).is_err());
+        assert!(ole_date_to_timestamp(Value::from("NaN"), None).is_err());
+    }
+
+    #[test]
+    fn sid() {
+        let name = |s: &str| sid_name(Value::from(s), None).unwrap();
+        assert_eq!(name("S-1-5-18"), Value::from("Local System"));
+        assert_eq!(name(" s-1-5-32-544 "), Value::from("Administrators"));
+        assert_eq!(name("S-1-5-21-1004336348-1177238915-682003330-500"), Value::from("Administrator"));
+        assert_eq!(name("S-1-5-21-1004336348-1177238915-682003330-512"), Value::from("Domain Admins"));
+        // Not a well-known RID, or not a domain SID
+        assert_eq!(name("S-1-5-21-1004336348-1177238915-682003330-1104"), Value::Null);
+        assert_eq!(name("S-1-5-21-1004336348-500"), Value::Null);
+        assert_eq!(name("S-1-5-99"), Value::Null);
+        assert_eq!(sid_name(Value::Integer(18), None).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn user_account_control() {
+        let flags = |v: Value| names(parse_user_account_control(v, None).unwrap());
+        assert_eq!(flags(Value::Integer(0x10200)), ["NORMAL_ACCOUNT", "DONT_EXPIRE_PASSWORD"]);
+        assert_eq!(flags(Value::from("514")), ["ACCOUNTDISABLE", "NORMAL_ACCOUNT"]);
+        assert_eq!(flags(Value::from("0x1000")), ["WORKSTATION_TRUST_ACCOUNT"]);
+        assert!(flags(Value::Integer(0)).is_empty());
+        // Unassigned bits are ignored
+        assert!(flags(Value::Integer(0x4)).is_empty());
+        assert!(parse_user_account_control(Value::from("disabled"), None).is_err());
+    }
+
+    #[test]
+    fn windows_guid() {
+        let guid = |v: Value, mixed: Option<bool>| parse_windows_guid(v, mixed.map(Value::from)).unwrap();
+        let raw = Value::Bytes((0u8..16).collect::<Vec<u8>>().into());
+
+        assert_eq!(guid(raw.clone(), None), Value::from("03020100-0504-0706-0809-0a0b0c0d0e0f"));
+        assert_eq!(guid(raw, Some(false)), Value::from("00010203-0405-0607-0809-0a0b0c0d0e0f"));
+        assert_eq!(
+            guid(Value::from("00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F"), None),
+            Value::from("03020100-0504-0706-0809-0a0b0c0d0e0f")
+        );
+        // Canonical form is kept as-is, whatever the byte order
+        assert_eq!(
+            guid(Value::from("{54849625-5478-4994-A5BA-3E3B0328C30D}"), Some(true)),
+            Value::from("54849625-5478-4994-a5ba-3e3b0328c30d")
+        );
+        assert!(parse_windows_guid(Value::from("0001020304"), None).is_err());
+        assert!(parse_windows_guid(Value::from("zz0102030405060708090a0b0c0d0e0f"), None).is_err());
+    }
+}
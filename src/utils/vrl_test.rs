@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::Value;
+use vrl::value::Value as VrlValue;
+use walkdir::WalkDir;
+
+use crate::utils::vrl::{VrlChain, VrlChainError, compile_vrl_chain, event_metadata, run_vrl_chain};
+
+/// Suffix of the test case files: `evtx.test.yml` holds the cases of `evtx.vrl`.
+const TEST_FILE_SUFFIX: &str = ".test.yml";
+
+/// One test case of a `<script>.test.yml` file (a YAML list of cases).
+///
+///   - name: 4625 failed logon
+///     input: {"Event": {...}}
+///     metadata: {host: "WS-01"}       # optional, over the default %file_path, %line...
+///     chain: [common.vrl, evtx.vrl]   # optional, scripts run in order (default: the script)
+///     expected: {"event": {...}}      # the whole normalized event
+///   - name: heartbeat is dropped
+///     input: {...}
+///     expected_dropped: true          # by an `abort`; a runtime error fails the case
+#[derive(Debug, Deserialize)]
+struct TestCase {
+    name: Option<String>,
+    input: Value,
+    #[serde(default)]
+    metadata: serde_json::Map<String, Value>,
+    chain: Option<Vec<String>>,
+    expected: Option<Value>,
+    #[serde(default)]
+    expected_dropped: bool,
+}
+
+/// Differences between the expected and the actual output, by field path:
+/// ".user.name: expected "bob", got "alice"".
+fn diff(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(e), Value::Object(a)) => {
+            for (key, ev) in e {
+                let sub = format!("{}.{}", path, key);
+                match a.get(key) {
+                    Some(av) => diff(&sub, ev, av, out),
+                    None => out.push(format!("{}: missing (expected {})", sub, ev)),
+                }
+            }
+            for (key, av) in a {
+                if !e.contains_key(key) {
+                    out.push(format!("{}.{}: unexpected {}", path, key, av));
+                }
+            }
+        }
+        (Value::Array(e), Value::Array(a)) if e.len() == a.len() => {
+            for (i, (ev, av)) in e.iter().zip(a).enumerate() {
+                diff(&format!("{}[{}]", path, i), ev, av, out);
+            }
+        }
+        _ if expected != actual => {
+            let path = if path.is_empty() { "." } else { path };
+            out.push(format!("{}: expected {}, got {}", path, expected, actual));
+        }
+        _ => {}
+    }
+}
+
+/// Compile a chain, failing if one of its scripts does not compile (compile_vrl_chain leaves
+/// it out of the chain).
+fn compile_chain(script_dir: &Path, paths: &[String]) -> Result<VrlChain, String> {
+    let chain = compile_vrl_chain(Some(script_dir), paths);
+    if chain.len() != paths.len() {
+        return Err(format!("the chain {:?} does not compile (see the errors above)", paths));
+    }
+    Ok(chain)
+}
+
+/// Run a case. Returns the failure lines, empty when it passes.
+fn run_case(case: &TestCase, chain: &Result<VrlChain, String>, test_file: &Path, n: usize) -> Vec<String> {
+    // 1. Expectation
+    let expected = match (&case.expected, case.expected_dropped) {
+        (Some(_), true) => return vec!["set either expected or expected_dropped, not both".to_string()],
+        (None, false) => return vec!["no expected output (set expected or expected_dropped: true)".to_string()],
+        (expected, _) => expected,
+    };
+
+    let chain = match chain {
+        Ok(chain) => chain,
+        Err(e) => return vec![e.clone()],
+    };
+
+    // 2. Metadata: the defaults of an ingestion, overridden by the case
+    let mut metadata = match event_metadata(&test_file.to_string_lossy(), n, "test", "test", "unknown") {
+        VrlValue::Object(m) => m,
+        _ => unreachable!("event_metadata returns an object"),
+    };
+    for (key, value) in &case.metadata {
+        match serde_json::from_value::<VrlValue>(value.clone()) {
+            Ok(v) => {
+                metadata.insert(key.as_str().into(), v);
+            }
+            Err(e) => return vec![format!("invalid metadata {}: {}", key, e)],
+        }
+    }
+
+    // 3. Run and compare
+    match (run_vrl_chain(case.input.clone(), chain, VrlValue::Object(metadata), false), expected) {
+        (Ok(_), None) => vec!["expected the event to be dropped, but it was kept".to_string()],
+        (Err(VrlChainError::Aborted), None) => Vec::new(),
+        (Err(VrlChainError::Aborted), Some(_)) => vec!["event dropped by an abort".to_string()],
+        (Err(VrlChainError::Runtime(e)), _) => vec![format!("event dropped by a runtime error:\n{}", e)],
+        (Err(VrlChainError::Invalid(e)), _) => vec![e],
+        (Ok((actual, _)), Some(expected)) => {
+            let mut out = Vec::new();
+            diff("", expected, &actual, &mut out);
+            out
+        }
+    }
+}
+
+/// Run the cases of every `<script>.test.yml` under `dir` against `<script>.vrl`.
+/// Returns false if a case fails or a test file cannot be read.
+pub fn run_vrl_tests(dir: &Path) -> bool {
+    let mut test_files: Vec<PathBuf> = Vec::new();
+    let mut scripts = 0usize;
+    let mut untested = Vec::new();
+
+    for entry in WalkDir::new(dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if name.ends_with(TEST_FILE_SUFFIX) {
+            test_files.push(path.to_path_buf());
+        } else if name.ends_with(".vrl") {
+            scripts += 1;
+            let stem = name.trim_end_matches(".vrl");
+            if !path.with_file_name(format!("{}{}", stem, TEST_FILE_SUFFIX)).exists() {
+                untested.push(path.to_path_buf());
+            }
+        }
+    }
+
+    if test_files.is_empty() {
+        error!("No *{} file found in {}", TEST_FILE_SUFFIX, dir.display());
+        return false;
+    }
+
+    let (mut passed, mut failed) = (0usize, 0usize);
+    let mut ok = true;
+
+    for test_file in &test_files {
+        let file_name = test_file.file_name().unwrap_or_default().to_string_lossy();
+        let script = format!("{}.vrl", file_name.trim_end_matches(TEST_FILE_SUFFIX));
+        let script_dir = test_file.parent().unwrap_or(Path::new("."));
+
+        let cases: Vec<TestCase> = match std::fs::read_to_string(test_file)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_yaml::from_str(&s).map_err(|e| e.to_string()))
+        {
+            Ok(cases) => cases,
+            Err(e) => {
+                error!("Cannot read test file {}: {}", test_file.display(), e);
+                ok = false;
+                continue;
+            }
+        };
+
+        // The script alone, compiled once for the cases without their own chain
+        let mut default_chain: Option<Result<VrlChain, String>> = None;
+
+        for (i, case) in cases.iter().enumerate() {
+            let name = case.name.clone().unwrap_or_else(|| format!("case {}", i + 1));
+            let custom_chain;
+            let chain = match case.chain {
+                Some(ref paths) => {
+                    custom_chain = compile_chain(script_dir, paths);
+                    &custom_chain
+                }
+                None => default_chain.get_or_insert_with(|| compile_chain(script_dir, std::slice::from_ref(&script))),
+            };
+            let failures = run_case(case, chain, test_file, i + 1);
+            if failures.is_empty() {
+                info!("PASS {} :: {}", test_file.display(), name);
+                passed += 1;
+            } else {
+                error!("FAIL {} :: {}", test_file.display(), name);
+                for line in failures {
+                    error!("    {}", line);
+                }
+                failed += 1;
+            }
+        }
+    }
+
+    for script in &untested {
+        warn!("No test cases for {}", script.display());
+    }
+    info!(
+        "VRL tests: {} passed, {} failed ({} test files, {} of {} scripts without tests)",
+        passed,
+        failed,
+        test_files.len(),
+        untested.len(),
+        scripts
+    );
+
+    ok && failed == 0
+}